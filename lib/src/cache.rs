@@ -0,0 +1,186 @@
+use std::{collections::HashMap, str::FromStr};
+
+use atuin_common::record::{HostId, RecordId, RecordIdx};
+use sqlx::{sqlite::SqlitePool, Row};
+
+use crate::{sqlite, Error, Note, Result};
+
+/// Decoded notes, persisted so that records only need to be decrypted once.
+///
+/// For each host we remember the index of the next record to load from the record store,
+/// as well as the records that could not be loaded so that they can be retried later,
+/// e.g. once the correct key is available.
+///
+/// Like the search index, this is only derived from the record store and can be deleted at any time.
+#[derive(Debug, Clone)]
+pub(crate) struct NoteCache {
+    pool: SqlitePool,
+}
+
+impl NoteCache {
+    pub(crate) async fn new(path: &str, timeout: f64) -> Result<Self> {
+        let pool = sqlite::open(path, timeout)
+            .await
+            .map_err(Error::index("opening note cache"))?;
+
+        for query in [
+            "create table if not exists notes (
+                id text primary key not null,
+                host text not null,
+                idx integer not null,
+                data blob not null
+            )",
+            "create table if not exists hosts (
+                host text primary key not null,
+                next_idx integer not null
+            )",
+            "create table if not exists skipped (
+                host text not null,
+                idx integer not null,
+                primary key (host, idx)
+            )",
+        ] {
+            sqlx::query(query)
+                .execute(&pool)
+                .await
+                .map_err(Error::index("creating note cache"))?;
+        }
+
+        Ok(Self { pool })
+    }
+
+    /// The index of the next record to load, for each host.
+    pub(crate) async fn hosts(&self) -> Result<HashMap<HostId, RecordIdx>> {
+        let rows = sqlx::query("select host, next_idx from hosts")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::index("loading cached hosts"))?;
+
+        rows.into_iter()
+            .map(|row| Ok((HostId(parse_uuid(row.get(0))?), row.get::<i64, _>(1) as u64)))
+            .collect()
+    }
+
+    /// All cached notes, in the order they were written by each host.
+    pub(crate) async fn notes(&self) -> Result<Vec<(HostId, RecordId, Note)>> {
+        let rows = sqlx::query("select host, id, data from notes order by host, idx")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::index("loading cached notes"))?;
+
+        rows.into_iter()
+            .map(|row| {
+                let host = HostId(parse_uuid(row.get(0))?);
+                let id = RecordId(parse_uuid(row.get(1))?);
+                let note = Note::deser_v1(row.get(2))?;
+                Ok((host, id, note))
+            })
+            .collect()
+    }
+
+    /// Records that previously failed to load.
+    pub(crate) async fn skipped(&self) -> Result<Vec<(HostId, RecordIdx)>> {
+        let rows = sqlx::query("select host, idx from skipped order by host, idx")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::index("loading skipped records"))?;
+
+        rows.into_iter()
+            .map(|row| Ok((HostId(parse_uuid(row.get(0))?), row.get::<i64, _>(1) as u64)))
+            .collect()
+    }
+
+    /// Remove retracted or deleted notes from the cache.
+    pub(crate) async fn remove(&self, ids: &[RecordId]) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(Error::index("starting note cache transaction"))?;
+
+        for id in ids {
+            sqlx::query("delete from notes where id = ?1")
+                .bind(id.0.as_hyphenated().to_string())
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::index("removing note"))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(Error::index("committing note cache transaction"))
+    }
+
+    /// Store a batch of records loaded from `host`.
+    ///
+    /// `loaded` are the notes that were decoded and `skipped` the indices of records that failed.
+    /// If `next_idx` is given, it is saved as the index of the next record to load from `host`.
+    pub(crate) async fn insert(
+        &self,
+        host: HostId,
+        loaded: &[(RecordIdx, RecordId, &Note)],
+        skipped: &[RecordIdx],
+        next_idx: Option<RecordIdx>,
+    ) -> Result<()> {
+        let host = host.0.as_hyphenated().to_string();
+
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(Error::index("starting note cache transaction"))?;
+
+        for (idx, id, note) in loaded {
+            sqlx::query(
+                "insert or replace into notes (id, host, idx, data) values (?1, ?2, ?3, ?4)",
+            )
+            .bind(id.0.as_hyphenated().to_string())
+            .bind(&host)
+            .bind(*idx as i64)
+            .bind(note.ser_v1_to_vec())
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::index("caching note"))?;
+
+            sqlx::query("delete from skipped where host = ?1 and idx = ?2")
+                .bind(&host)
+                .bind(*idx as i64)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::index("caching note"))?;
+        }
+
+        for idx in skipped {
+            sqlx::query("insert or ignore into skipped (host, idx) values (?1, ?2)")
+                .bind(&host)
+                .bind(*idx as i64)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::index("caching skipped record"))?;
+        }
+
+        if let Some(next_idx) = next_idx {
+            sqlx::query(
+                "insert into hosts (host, next_idx) values (?1, ?2)
+                on conflict (host) do update set next_idx = max(next_idx, excluded.next_idx)",
+            )
+            .bind(&host)
+            .bind(next_idx as i64)
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::index("caching host index"))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(Error::index("committing note cache transaction"))
+    }
+}
+
+fn parse_uuid(s: &str) -> Result<uuid::Uuid> {
+    uuid::Uuid::from_str(s).map_err(Error::index("parsing cached id"))
+}
@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+
+use atuin_client::record::encryption::PASETO_V4;
+use atuin_common::record::{EncryptedData, Record, RecordId};
+use ciborium_ll::{Decoder, Encoder};
+use jiff::{Span, Timestamp};
+
+use crate::{children_deser, children_ser, Error, NoteTree, Result, RollupPeriod};
+
+/// Records listing note records deleted by [`Client::compact`](crate::Client::compact),
+/// so that other hosts delete them too.
+pub(crate) const COMPACTION_TAG: &str = "note_lsm::compaction";
+pub(crate) const COMPACTION_VERSION: &str = "v0";
+
+/// Which notes to delete in [`Client::compact`](crate::Client::compact).
+#[derive(Debug, Clone, Copy)]
+pub struct CompactOptions {
+    /// Only compact merge notes written at least this long ago. Negative ages are rejected.
+    ///
+    /// Rollups are never compacted, as they are written with the time of the notes they merge.
+    pub min_age: Span,
+    /// Record which notes were deleted, so that other hosts delete them when they next sync.
+    pub propagate: bool,
+    /// Only report which notes would be deleted, without deleting them.
+    pub dry_run: bool,
+}
+
+impl Default for CompactOptions {
+    fn default() -> Self {
+        Self {
+            min_age: Span::new().days(30),
+            propagate: false,
+            dry_run: false,
+        }
+    }
+}
+
+/// The result of [`Client::compact`](crate::Client::compact).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactSummary {
+    /// The merge notes whose descendants were deleted.
+    pub merges: Vec<RecordId>,
+    /// The deleted note records, or the ones that would be deleted in a dry run.
+    pub deleted: Vec<RecordId>,
+}
+
+/// The merge notes written before `cutoff`, and the loaded notes merged into them that can be deleted.
+///
+/// Rollups are skipped, as their time is taken from the notes they merge rather than when they were
+/// written, so they would be compacted as soon as they are added.
+///
+/// Notes that were also merged into a note that is not being compacted are kept,
+/// along with the notes merged into them.
+pub(crate) fn compactable(
+    tree: &NoteTree,
+    cutoff: Timestamp,
+) -> (Vec<RecordId>, HashSet<RecordId>) {
+    let old: HashSet<_> = tree
+        .iter()
+        .filter(|(_, note)| {
+            !note.children.is_empty()
+                && !RollupPeriod::is_rollup(note)
+                && note.datetime.timestamp() < cutoff
+        })
+        .map(|(id, _)| id)
+        .collect();
+
+    let mut delete: HashSet<_> = old
+        .iter()
+        .flat_map(|&id| tree.descendants(id))
+        .filter(|&id| tree.get(id).is_some())
+        .collect();
+    loop {
+        let keep: Vec<_> = delete
+            .iter()
+            .copied()
+            .filter(|&id| {
+                tree.parents_of(id)
+                    .iter()
+                    .any(|parent| !delete.contains(parent) && !old.contains(parent))
+            })
+            .collect();
+        if keep.is_empty() {
+            break;
+        }
+        for id in keep {
+            delete.remove(&id);
+        }
+    }
+
+    let mut merges: Vec<_> = old
+        .into_iter()
+        .filter(|&id| !delete.contains(&id))
+        .filter(|&id| {
+            let note = tree.get(id).expect("merge notes are loaded");
+            note.children.iter().any(|child| delete.contains(child))
+        })
+        .collect();
+    merges.sort();
+
+    (merges, delete)
+}
+
+/// Encode the ids of deleted records for a compaction record.
+pub(crate) fn encode(ids: &[RecordId]) -> Vec<u8> {
+    let mut buf = vec![];
+    children_ser(ids, &mut Encoder::from(&mut buf)).expect("encoding to vec should not fail");
+    buf
+}
+
+/// Decrypt a compaction record, returning the ids of the deleted records.
+pub(crate) fn decrypt(record: Record<EncryptedData>, key: &[u8; 32]) -> Result<Vec<RecordId>> {
+    let id = record.id;
+    if record.version != COMPACTION_VERSION {
+        return Err(Error::UnknownVersion {
+            id,
+            version: record.version,
+        });
+    }
+
+    let record = record
+        .decrypt::<PASETO_V4>(key)
+        .map_err(|err| Error::Decrypt {
+            id,
+            source: err.into(),
+        })?;
+
+    children_deser(&mut Decoder::from(&record.data.0[..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::{civil::datetime, tz::TimeZone};
+
+    use super::compactable;
+    use crate::{
+        test_util::{id, note_at, tagged, HOST},
+        Note, NoteTree,
+    };
+
+    /// A note written at noon UTC on `day` of November 2024.
+    fn note(day: i8, children: &[u128]) -> Note {
+        let datetime = datetime(2024, 11, day, 12, 0, 0, 0)
+            .to_zoned(TimeZone::UTC)
+            .unwrap();
+        note_at("note", datetime, children)
+    }
+
+    #[test]
+    fn compact() {
+        let mut tree = NoteTree::new();
+        tree.insert(HOST, id(1), note(1, &[]));
+        tree.insert(HOST, id(2), note(1, &[]));
+        tree.insert(HOST, id(3), note(1, &[]));
+        tree.insert(HOST, id(4), note(2, &[1, 2, 9]));
+        tree.insert(HOST, id(5), note(3, &[4]));
+        // a newer merge that also includes note 2
+        tree.insert(HOST, id(6), note(20, &[2, 3]));
+
+        let (merges, delete) = compactable(&tree, "2024-11-10T00:00Z".parse().unwrap());
+        assert_eq!(merges, [id(5)]);
+        let mut delete: Vec<_> = delete.into_iter().collect();
+        delete.sort();
+        assert_eq!(delete, [id(1), id(4)]);
+
+        let (merges, delete) = compactable(&tree, "2024-11-30T00:00Z".parse().unwrap());
+        assert_eq!(merges, [id(5), id(6)]);
+        assert_eq!(delete.len(), 4);
+    }
+
+    #[test]
+    fn rollups() {
+        let mut tree = NoteTree::new();
+        tree.insert(HOST, id(1), note(1, &[]));
+        tree.insert(HOST, id(2), note(1, &[]));
+        tree.insert(HOST, id(3), tagged(note(1, &[1, 2]), &["rollup/daily"]));
+        tree.insert(HOST, id(4), note(2, &[]));
+        tree.insert(HOST, id(5), tagged(note(7, &[3, 4]), &["rollup/weekly"]));
+
+        // rollups take the time of the notes they merge, so they are not compacted by age
+        let (merges, delete) = compactable(&tree, "2024-11-30T00:00Z".parse().unwrap());
+        assert!(merges.is_empty());
+        assert!(delete.is_empty());
+
+        // but merging a rollup by hand compacts it like any other note
+        tree.insert(HOST, id(6), note(8, &[5]));
+        let (merges, delete) = compactable(&tree, "2024-11-30T00:00Z".parse().unwrap());
+        assert_eq!(merges, [id(6)]);
+        assert_eq!(delete.len(), 5);
+    }
+}
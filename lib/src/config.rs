@@ -0,0 +1,261 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use atuin_client::settings::Settings;
+use serde::Deserialize;
+
+use crate::{Error, LevelPolicy, Result, RollupPeriod};
+
+/// The contents of the note-lsm config file.
+///
+/// ```toml
+/// # where to keep the record store and local indices, defaults to $XDG_DATA_HOME/notelsm
+/// data_dir = "~/notes"
+/// # the profile to use when none is given
+/// default_profile = "work"
+/// # rollups the app creates in the background, see `plan_rollups`
+/// rollups = ["daily", "weekly"]
+///
+/// # the number of unprocessed notes at each level before they are due for review, see `LevelPolicy`
+/// [levels]
+/// budgets = [30, 14, 8]
+///
+/// [profiles.work]
+/// # defaults to <data_dir>/profiles/work
+/// data_dir = "~/work/notes"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    data_dir: Option<PathBuf>,
+    default_profile: Option<String>,
+    rollups: Vec<RollupPeriod>,
+    levels: LevelPolicy,
+    profiles: BTreeMap<String, ProfileFile>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProfileFile {
+    data_dir: Option<PathBuf>,
+}
+
+/// Where note-lsm keeps its data, resolved from the config file and environment.
+///
+/// The config file is read from the path given to [`Config::load`], `$NOTE_LSM_CONFIG`,
+/// or `$XDG_CONFIG_HOME/note-lsm/config.toml`, in that order.
+/// `$NOTE_LSM_DATA_DIR` and `$NOTE_LSM_PROFILE` override the values in the file,
+/// with profiles kept in `$NOTE_LSM_DATA_DIR/profiles/<name>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// The selected profile, if any.
+    pub profile: Option<String>,
+    /// The directory holding the record store and local indices for the selected profile.
+    pub data_dir: PathBuf,
+    /// The rollups to create automatically, see [`plan_rollups`](crate::plan_rollups).
+    pub rollups: Vec<RollupPeriod>,
+    /// When unprocessed notes are due for review.
+    pub levels: LevelPolicy,
+}
+
+impl Config {
+    /// Load the config, using the named profile instead of the default one if given.
+    pub fn load(path: Option<&Path>, profile: Option<&str>) -> Result<Self> {
+        Self::load_with_env(path, profile, |key| std::env::var_os(key))
+    }
+
+    fn load_with_env(
+        path: Option<&Path>,
+        profile: Option<&str>,
+        env: impl Fn(&str) -> Option<OsString>,
+    ) -> Result<Self> {
+        let home = env("HOME").map(PathBuf::from);
+
+        // an explicitly chosen config file must exist, the default one is optional
+        let (path, required) = match path {
+            Some(path) => (path.to_owned(), true),
+            None => match env("NOTE_LSM_CONFIG") {
+                Some(path) => (PathBuf::from(path), true),
+                None => (
+                    xdg_dir(&env, "XDG_CONFIG_HOME", home.as_deref(), ".config")?
+                        .join("note-lsm")
+                        .join("config.toml"),
+                    false,
+                ),
+            },
+        };
+
+        let file = match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).map_err(|err| Error::Config {
+                path: path.clone(),
+                source: err.into(),
+            })?,
+            Err(err) if !required && err.kind() == std::io::ErrorKind::NotFound => {
+                ConfigFile::default()
+            }
+            Err(err) => {
+                return Err(Error::Config {
+                    path,
+                    source: err.into(),
+                })
+            }
+        };
+
+        let env_profile = env("NOTE_LSM_PROFILE").and_then(|p| p.into_string().ok());
+        let profile = profile
+            .map(str::to_owned)
+            .or(env_profile)
+            .or(file.default_profile);
+
+        let env_data_dir = env("NOTE_LSM_DATA_DIR").map(PathBuf::from);
+        let data_dir = match &env_data_dir {
+            Some(dir) => dir.clone(),
+            None => match file.data_dir {
+                Some(dir) => expand_home(dir, home.as_deref()),
+                None => {
+                    xdg_dir(&env, "XDG_DATA_HOME", home.as_deref(), ".local/share")?.join("notelsm")
+                }
+            },
+        };
+
+        let data_dir = match &profile {
+            None => data_dir,
+            Some(name) => match file.profiles.get(name) {
+                Some(ProfileFile {
+                    data_dir: Some(dir),
+                }) if env_data_dir.is_none() => expand_home(dir.clone(), home.as_deref()),
+                Some(_) => data_dir.join("profiles").join(name),
+                None => return Err(Error::UnknownProfile(name.clone())),
+            },
+        };
+
+        Ok(Self {
+            profile,
+            data_dir,
+            rollups: file.rollups,
+            levels: file.levels,
+        })
+    }
+
+    /// The path of the atuin record store.
+    pub fn store_path(&self) -> PathBuf {
+        self.data_dir.join("store.db")
+    }
+
+    /// The atuin settings, with the record store moved into our data directory.
+    pub fn settings(&self) -> Result<Settings> {
+        let mut settings = Settings::new().map_err(|err| Error::Settings(err.into()))?;
+        settings.record_store_path = self
+            .store_path()
+            .to_str()
+            .ok_or_else(|| Error::Config {
+                path: self.data_dir.clone(),
+                source: "data directory is not valid UTF-8".into(),
+            })?
+            .to_owned();
+        Ok(settings)
+    }
+}
+
+/// Resolve an XDG base directory, falling back to `$HOME/<default>`.
+fn xdg_dir(
+    env: &impl Fn(&str) -> Option<OsString>,
+    var: &str,
+    home: Option<&Path>,
+    default: &str,
+) -> Result<PathBuf> {
+    match env(var) {
+        // relative paths are invalid according to the XDG spec and should be ignored
+        Some(dir) if Path::new(&dir).is_absolute() => Ok(PathBuf::from(dir)),
+        _ => home.map(|home| home.join(default)).ok_or(Error::HomeDir),
+    }
+}
+
+fn expand_home(path: PathBuf, home: Option<&Path>) -> PathBuf {
+    match (path.strip_prefix("~"), home) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, path::PathBuf};
+
+    use super::Config;
+    use crate::{Error, LevelPolicy, RollupPeriod};
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<OsString> + 'a {
+        |key| {
+            vars.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| OsString::from(v))
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let config = Config::load_with_env(None, None, env(&[("HOME", "/home/me")])).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                profile: None,
+                data_dir: PathBuf::from("/home/me/.local/share/notelsm"),
+                rollups: vec![],
+                levels: LevelPolicy::default(),
+            }
+        );
+
+        let vars = [("HOME", "/home/me"), ("XDG_DATA_HOME", "/data")];
+        let config = Config::load_with_env(None, None, env(&vars)).unwrap();
+        assert_eq!(config.store_path(), PathBuf::from("/data/notelsm/store.db"));
+    }
+
+    #[test]
+    fn file_and_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+                data_dir = "~/notes"
+                default_profile = "home"
+                rollups = ["daily"]
+                levels.budgets = [10]
+
+                [profiles.home]
+                [profiles.work]
+                data_dir = "/work/notes"
+            "#,
+        )
+        .unwrap();
+        let vars = [("HOME", "/home/me")];
+
+        let config = Config::load_with_env(Some(&path), None, env(&vars)).unwrap();
+        assert_eq!(config.profile.as_deref(), Some("home"));
+        assert_eq!(config.rollups, [RollupPeriod::Daily]);
+        assert_eq!(config.levels.budgets, [10]);
+        assert_eq!(
+            config.data_dir,
+            PathBuf::from("/home/me/notes/profiles/home")
+        );
+
+        let config = Config::load_with_env(Some(&path), Some("work"), env(&vars)).unwrap();
+        assert_eq!(config.data_dir, PathBuf::from("/work/notes"));
+
+        let vars = [("HOME", "/home/me"), ("NOTE_LSM_DATA_DIR", "/data")];
+        let config = Config::load_with_env(Some(&path), Some("work"), env(&vars)).unwrap();
+        assert_eq!(config.data_dir, PathBuf::from("/data/profiles/work"));
+
+        let vars = [("HOME", "/home/me"), ("NOTE_LSM_PROFILE", "missing")];
+        let err = Config::load_with_env(Some(&path), None, env(&vars)).unwrap_err();
+        assert!(matches!(err, Error::UnknownProfile(name) if name == "missing"));
+
+        let missing = dir.path().join("missing.toml");
+        let err = Config::load_with_env(Some(&missing), None, env(&vars)).unwrap_err();
+        assert!(matches!(err, Error::Config { .. }));
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use atuin_common::record::RecordId;
+use jiff::{
+    civil::{Date, Time},
+    tz::TimeZone,
+    Zoned,
+};
+use uuid::Uuid;
+
+use crate::{Error, Note, Result, RollupPeriod};
+
+/// Notes read from a daily notes file, such as an Obsidian daily note.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailyNotes {
+    pub date: Date,
+    /// Each top-level bullet as a separate note, in the order they were written.
+    pub notes: Vec<(RecordId, Note)>,
+    /// All other text in the file, such as headings and paragraphs.
+    pub rest: String,
+}
+
+impl DailyNotes {
+    /// Parse the contents of the daily notes file for `date`.
+    ///
+    /// Every top-level bullet (`-`, `*` or `+`) becomes a note, including any indented lines below it.
+    /// A bullet starting with a time such as `09:30` or `[09:30]` is timestamped at that time,
+    /// other bullets are timestamped a second after the previous bullet, starting at midnight.
+    ///
+    /// Note ids are derived from the date, the written time and the text, so importing the file
+    /// again with [`Client::import`](crate::Client::import) skips the notes that were already
+    /// imported, even after bullets were added or removed around them.
+    pub fn parse(date: Date, contents: &str, tz: &TimeZone) -> Result<Self> {
+        let mut bullets: Vec<(Option<Time>, String)> = vec![];
+        let mut rest = vec![];
+        let mut in_bullet = false;
+
+        for line in contents.lines() {
+            let indented = line.starts_with([' ', '\t']);
+            if let Some(text) = strip_bullet(line) {
+                let (time, text) = strip_time(text);
+                bullets.push((time, text.to_owned()));
+                in_bullet = true;
+            } else if in_bullet && indented {
+                let (_, text) = bullets.last_mut().expect("in a bullet");
+                text.push('\n');
+                text.push_str(line.trim());
+            } else {
+                in_bullet = false;
+                rest.push(line);
+            }
+        }
+
+        let to_zoned = |time: Time| {
+            date.to_datetime(time)
+                .to_zoned(tz.clone())
+                .map_err(|source| Error::TimeZone {
+                    name: tz.iana_name().unwrap_or_default().to_owned(),
+                    source,
+                })
+        };
+
+        let mut notes = vec![];
+        let mut seen = HashMap::<_, usize>::new();
+        let mut last = to_zoned(Time::midnight())?;
+        for (i, (time, text)) in bullets.into_iter().enumerate() {
+            let datetime = match time {
+                Some(time) => to_zoned(time)?,
+                None if i == 0 => last.clone(),
+                None => last.saturating_add(jiff::Span::new().seconds(1)),
+            };
+            last = datetime.clone();
+
+            // ids are timestamped like the notes so they sort the same way, and hash the bullet
+            // rather than its position. The same bullet can be written twice on one day, so count
+            // them to keep ids unique.
+            let n = seen.entry((time, text.clone())).or_default();
+            *n += 1;
+            let key = match time {
+                Some(time) => format!("{date} {time} {text} {n}"),
+                None => format!("{date} {text} {n}"),
+            };
+            let id = daily_id(&datetime, &key);
+            notes.push((
+                id,
+                Note {
+                    note: text,
+                    datetime,
+                    children: vec![],
+                    tags: vec![],
+                    retracts: vec![],
+                },
+            ));
+        }
+
+        Ok(Self {
+            date,
+            notes,
+            rest: rest.join("\n").trim().to_owned(),
+        })
+    }
+
+    /// A note for the whole day, with all bullets as its children.
+    ///
+    /// Its text is the rest of the file, or the date if there is none.
+    /// It is written a second after the latest bullet, and tagged as a daily rollup
+    /// so that [`plan_rollups`](crate::plan_rollups) leaves the day alone.
+    pub fn merge_note(&self) -> Option<(RecordId, Note)> {
+        let latest = self.notes.iter().map(|(_, note)| &note.datetime).max()?;
+        let datetime = latest.saturating_add(jiff::Span::new().seconds(1));
+        let text = if self.rest.is_empty() {
+            self.date.to_string()
+        } else {
+            self.rest.clone()
+        };
+
+        // the id only depends on the date, so adding a bullet does not change it,
+        // and uses the end of the day so that it still sorts after the bullets
+        let end_of_day = self
+            .date
+            .to_datetime(Time::MAX)
+            .to_zoned(datetime.time_zone().clone())
+            .unwrap_or_else(|_| datetime.clone());
+        let id = daily_id(&end_of_day, &format!("{} merge", self.date));
+        Some((
+            id,
+            Note {
+                note: text,
+                datetime,
+                children: self.notes.iter().map(|(id, _)| *id).collect(),
+                tags: vec![RollupPeriod::Daily.tag().to_owned()],
+                retracts: vec![],
+            },
+        ))
+    }
+}
+
+/// Read all `YYYY-MM-DD.md` files in `dir` and its subdirectories, ordered by date.
+pub fn read_daily_notes(dir: &Path, tz: &TimeZone) -> Result<Vec<DailyNotes>> {
+    let mut files = vec![];
+    find_daily_files(dir, &mut files)?;
+    files.sort();
+
+    files
+        .into_iter()
+        .map(|(date, path)| {
+            let contents = std::fs::read_to_string(&path).map_err(|source| Error::Io {
+                path: path.clone(),
+                source,
+            })?;
+            DailyNotes::parse(date, &contents, tz)
+        })
+        .collect()
+}
+
+fn find_daily_files(dir: &Path, files: &mut Vec<(Date, PathBuf)>) -> Result<()> {
+    let io_err = |source| Error::Io {
+        path: dir.to_owned(),
+        source,
+    };
+
+    for entry in std::fs::read_dir(dir).map_err(io_err)? {
+        let entry = entry.map_err(io_err)?;
+        let path = entry.path();
+        if entry.file_type().map_err(io_err)?.is_dir() {
+            find_daily_files(&path, files)?;
+            continue;
+        }
+
+        let date = path
+            .file_name()
+            .and_then(|name| name.to_str()?.strip_suffix(".md"))
+            .and_then(|name| name.parse::<Date>().ok());
+        if let Some(date) = date {
+            files.push((date, path));
+        }
+    }
+
+    Ok(())
+}
+
+fn strip_bullet(line: &str) -> Option<&str> {
+    let text = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .or_else(|| line.strip_prefix("+ "))?;
+    Some(text.trim())
+}
+
+/// Split a leading `HH:MM` time off a bullet, optionally wrapped in `[]` or `**`
+/// and followed by `-`.
+fn strip_time(text: &str) -> (Option<Time>, &str) {
+    let Some((first, rest)) = text.split_once(' ') else {
+        return (None, text);
+    };
+    let first = first
+        .trim_start_matches(['[', '*'])
+        .trim_end_matches([']', '*']);
+    // require `HH:MM` so that text like `10:1` is kept as is
+    if first.len() != 5 {
+        return (None, text);
+    }
+
+    match first.parse::<Time>() {
+        Ok(time) => {
+            let rest = rest.trim_start();
+            let rest = rest.strip_prefix("- ").unwrap_or(rest);
+            (Some(time), rest.trim_start())
+        }
+        Err(_) => (None, text),
+    }
+}
+
+/// A v7 uuid for the note time, with the random bits derived from `key` so that it is stable.
+fn daily_id(datetime: &Zoned, key: &str) -> RecordId {
+    let hash = Uuid::new_v5(&Uuid::NAMESPACE_OID, key.as_bytes());
+    let random: [u8; 10] = hash.as_bytes()[6..].try_into().expect("10 bytes");
+    let millis = datetime.timestamp().as_millisecond().max(0) as u64;
+    RecordId(uuid::Builder::from_unix_timestamp_millis(millis, &random).into_uuid())
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::{
+        civil::{date, time},
+        tz::TimeZone,
+    };
+
+    use super::{strip_time, DailyNotes};
+
+    #[test]
+    fn times() {
+        assert_eq!(
+            strip_time("09:30 standup"),
+            (Some(time(9, 30, 0, 0)), "standup")
+        );
+        assert_eq!(
+            strip_time("[09:30] standup"),
+            (Some(time(9, 30, 0, 0)), "standup")
+        );
+        assert_eq!(
+            strip_time("**14:05** - lunch"),
+            (Some(time(14, 5, 0, 0)), "lunch")
+        );
+        assert_eq!(strip_time("10:1 ratio"), (None, "10:1 ratio"));
+        assert_eq!(strip_time("25:00 late"), (None, "25:00 late"));
+    }
+
+    #[test]
+    fn parse() {
+        let tz = TimeZone::get("Europe/London").unwrap();
+        let day = date(2024, 11, 9);
+        let contents = "\
+# Saturday
+
+- first thing
+- 09:30 standup
+  - ask about the release
+- [ ] water plants
+
+Quiet day.
+* 14:05 - lunch
+";
+        let daily = DailyNotes::parse(day, contents, &tz).unwrap();
+
+        let notes: Vec<_> = daily
+            .notes
+            .iter()
+            .map(|(_, note)| (note.datetime.time(), note.note.as_str()))
+            .collect();
+        assert_eq!(
+            notes,
+            [
+                (time(0, 0, 0, 0), "first thing"),
+                (time(9, 30, 0, 0), "standup\n- ask about the release"),
+                (time(9, 30, 1, 0), "[ ] water plants"),
+                (time(14, 5, 0, 0), "lunch"),
+            ]
+        );
+        assert_eq!(daily.rest, "# Saturday\n\n\nQuiet day.");
+
+        // ids are stable
+        let again = DailyNotes::parse(day, contents, &tz).unwrap();
+        assert_eq!(daily, again);
+
+        let (_, merge) = daily.merge_note().unwrap();
+        assert_eq!(merge.children.len(), 4);
+        assert_eq!(merge.datetime.time(), time(14, 5, 1, 0));
+        assert_eq!(merge.note, daily.rest);
+        assert_eq!(merge.tags, ["rollup/daily"]);
+
+        // ids sort like the notes, including those without a time
+        let ids = |daily: &DailyNotes| daily.notes.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        let mut sorted = ids(&daily);
+        sorted.sort();
+        assert_eq!(sorted, ids(&daily));
+
+        // adding a bullet only changes the ids of the bullets without a time that follow it
+        let edited = contents.replace("- 09:30", "- 08:00 breakfast\n- 09:30");
+        let edited = DailyNotes::parse(day, &edited, &tz).unwrap();
+        assert_eq!(ids(&edited)[..1], ids(&daily)[..1]);
+        assert_eq!(ids(&edited)[2..], ids(&daily)[1..]);
+        let edited = format!("{contents}- coffee\n");
+        let edited = DailyNotes::parse(day, &edited, &tz).unwrap();
+        assert_eq!(ids(&edited)[..4], ids(&daily));
+        assert_eq!(
+            edited.merge_note().unwrap().0,
+            daily.merge_note().unwrap().0
+        );
+
+        // repeated bullets get their own ids
+        let repeated = DailyNotes::parse(day, "- coffee\n- coffee", &tz).unwrap();
+        assert_ne!(ids(&repeated)[0], ids(&repeated)[1]);
+    }
+
+    #[test]
+    fn merge_after_latest() {
+        let daily = DailyNotes::parse(
+            date(2024, 11, 9),
+            "- 14:00 lunch\n- 09:00 standup",
+            &TimeZone::UTC,
+        )
+        .unwrap();
+        let (_, merge) = daily.merge_note().unwrap();
+        assert_eq!(merge.datetime.time(), time(14, 0, 1, 0));
+    }
+}
@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+use atuin_client::record::sync::SyncError;
+use atuin_common::record::RecordId;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Errors returned by the note-lsm client.
+///
+/// New variants may be added in minor releases.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The local atuin record store could not be read from or written to.
+    #[error("{context}")]
+    Store {
+        context: &'static str,
+        #[source]
+        source: BoxError,
+    },
+
+    /// The local search index or note cache could not be read from or written to.
+    #[error("{context}")]
+    Index {
+        context: &'static str,
+        #[source]
+        source: BoxError,
+    },
+
+    /// The note-lsm config file could not be read or is invalid.
+    #[error("loading config {}", path.display())]
+    Config {
+        path: PathBuf,
+        #[source]
+        source: BoxError,
+    },
+
+    /// The selected profile is not defined in the config file.
+    #[error("unknown profile {0:?}")]
+    UnknownProfile(String),
+
+    /// The home directory, needed to find the default config and data directories, is not set.
+    #[error("$HOME not found")]
+    HomeDir,
+
+    /// The atuin settings could not be loaded.
+    #[error("loading atuin settings")]
+    Settings(#[source] BoxError),
+
+    /// Notebook names may only contain ASCII letters, digits, `-` and `_`.
+    #[error("invalid notebook name {0:?}")]
+    InvalidNotebook(String),
+
+    /// [`CompactOptions::min_age`](crate::CompactOptions::min_age) was negative.
+    #[error("compaction age must not be negative")]
+    NegativeAge,
+
+    /// The atuin host id for this machine could not be determined.
+    #[error("could not determine host id")]
+    HostId,
+
+    /// The atuin encryption key could not be loaded.
+    #[error("loading encryption key")]
+    Key(#[source] BoxError),
+
+    /// Syncing the record store with the atuin server failed.
+    #[error("syncing record store")]
+    Sync(#[source] SyncError),
+
+    /// The record could not be decrypted, likely because it was written with a different key.
+    #[error("decrypting record {}", id.0)]
+    Decrypt {
+        id: RecordId,
+        #[source]
+        source: BoxError,
+    },
+
+    /// The record was written with a version of the note format we do not understand.
+    #[error("record {} has unknown version {version:?}", id.0)]
+    UnknownVersion { id: RecordId, version: String },
+
+    /// The decrypted record contents are not a valid note encoding.
+    #[error("decoding note at byte {offset}: {reason}")]
+    Decode { offset: usize, reason: String },
+
+    /// A line of a JSON Lines export could not be parsed.
+    #[error("parsing line {line} of import")]
+    Import {
+        line: usize,
+        #[source]
+        source: BoxError,
+    },
+
+    /// A file to import could not be read.
+    #[error("reading {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The time zone attached to a note could not be resolved.
+    #[error("resolving time zone {name:?}")]
+    TimeZone {
+        name: String,
+        #[source]
+        source: jiff::Error,
+    },
+}
+
+impl Error {
+    pub(crate) fn store(context: &'static str) -> impl FnOnce(eyre::Report) -> Self {
+        move |source| Self::Store {
+            context,
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn index<E: Into<BoxError>>(context: &'static str) -> impl FnOnce(E) -> Self {
+        move |source| Self::Index {
+            context,
+            source: source.into(),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::fmt::Write;
+
+use atuin_common::record::{HostId, RecordId, RecordIdx};
+use jiff::{Timestamp, Zoned};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Note, NoteRecord, NoteTree, Result, Subtree};
+
+/// Which notes to export.
+#[derive(Debug, Default, Clone)]
+pub struct ExportOptions {
+    /// Only export this note and the notes merged into it,
+    /// instead of all notes that have not been merged.
+    pub root: Option<RecordId>,
+    /// Only export top-level notes written at or after this time.
+    pub since: Option<Timestamp>,
+    /// Only export top-level notes written before this time.
+    pub until: Option<Timestamp>,
+}
+
+impl ExportOptions {
+    /// The top-level notes to export, oldest first.
+    fn roots(&self, tree: &NoteTree) -> Vec<RecordId> {
+        match self.root {
+            Some(root) => match tree.get(root) {
+                Some(note) if !self.in_range(note) => vec![],
+                _ => vec![root],
+            },
+            None => tree
+                .query()
+                .unprocessed()
+                .since(self.since)
+                .until(self.until)
+                .iter()
+                .map(|(_, id, _)| id)
+                .collect(),
+        }
+    }
+
+    /// Whether the note was written between `since` and `until`.
+    fn in_range(&self, note: &Note) -> bool {
+        let time = note.datetime.timestamp();
+        self.since.is_none_or(|since| time >= since) && self.until.is_none_or(|until| time < until)
+    }
+}
+
+/// Render notes as Markdown.
+///
+/// Merged notes become headings, followed by the notes merged into them as nested bullets.
+/// Notes that were never merged are rendered as a single bullet.
+pub fn to_markdown(tree: &NoteTree, options: &ExportOptions) -> String {
+    let mut out = String::new();
+
+    for id in options.roots(tree) {
+        match tree.subtree(id) {
+            Subtree::Note { note, children, .. } if !children.is_empty() => {
+                let mut lines = note.note.lines();
+                let title = lines.next().unwrap_or_default();
+                writeln!(out, "## {title}\n").unwrap();
+                writeln!(out, "*{}*\n", time(&note.datetime)).unwrap();
+
+                let body = lines.collect::<Vec<_>>().join("\n");
+                let body = body.trim();
+                if !body.is_empty() {
+                    writeln!(out, "{body}\n").unwrap();
+                }
+
+                for child in &children {
+                    bullet(&mut out, child, 0);
+                }
+                out.push('\n');
+            }
+            Subtree::Retracted(_) => {}
+            subtree => {
+                bullet(&mut out, &subtree, 0);
+                out.push('\n');
+            }
+        }
+    }
+
+    out
+}
+
+fn bullet(out: &mut String, subtree: &Subtree<'_>, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match subtree {
+        Subtree::Note { note, children, .. } => {
+            let mut lines = note.note.lines();
+            let first = lines.next().unwrap_or_default();
+            writeln!(out, "{indent}- {} — {first}", time(&note.datetime)).unwrap();
+            // continuation lines must be indented to stay part of the list item
+            for line in lines {
+                writeln!(out, "{indent}  {line}").unwrap();
+            }
+            for child in children {
+                bullet(out, child, depth + 1);
+            }
+        }
+        Subtree::Missing(id) => writeln!(out, "{indent}- *missing note {}*", id.0).unwrap(),
+        Subtree::Cycle(id) => writeln!(out, "{indent}- *see note {} above*", id.0).unwrap(),
+        Subtree::Retracted(_) => {}
+    }
+}
+
+/// A line of a JSON Lines export.
+#[derive(Serialize, Deserialize)]
+struct JsonlNote {
+    id: RecordId,
+    host: HostId,
+    idx: RecordIdx,
+    /// RFC 9557, e.g. `2024-11-09T12:19:22+01:00[Europe/Paris]`
+    datetime: String,
+    text: String,
+    children: Vec<RecordId>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// Render note records as JSON Lines, one note per line.
+pub fn to_jsonl(records: &[NoteRecord]) -> String {
+    let mut out = String::new();
+    for record in records {
+        let line = JsonlNote {
+            id: record.id,
+            host: record.host,
+            idx: record.idx,
+            datetime: record.note.datetime.to_string(),
+            text: record.note.note.clone(),
+            children: record.note.children.clone(),
+            tags: record.note.tags.clone(),
+        };
+        out.push_str(
+            &serde_json::to_string(&line).expect("serializing to a string should not fail"),
+        );
+        out.push('\n');
+    }
+    out
+}
+
+/// Parse notes exported by [`to_jsonl`], ready for [`Client::import`](crate::Client::import).
+///
+/// The original host and index are not kept, the notes are re-recorded by the importing host.
+pub fn from_jsonl(input: &str) -> Result<Vec<(RecordId, Note)>> {
+    let mut notes = vec![];
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let import_err = |source: Box<dyn std::error::Error + Send + Sync>| Error::Import {
+            line: i + 1,
+            source,
+        };
+
+        let line: JsonlNote = serde_json::from_str(line).map_err(|err| import_err(err.into()))?;
+        let datetime = line
+            .datetime
+            .parse::<Zoned>()
+            .map_err(|err| import_err(err.into()))?;
+        notes.push((
+            line.id,
+            Note {
+                note: line.text,
+                datetime,
+                children: line.children,
+                tags: line.tags,
+                retracts: vec![],
+            },
+        ));
+    }
+    Ok(notes)
+}
+
+fn time(datetime: &Zoned) -> String {
+    datetime.strftime("%Y-%m-%d %H:%M").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_jsonl, to_jsonl, to_markdown, ExportOptions};
+    use crate::{
+        test_util::{id, note, tagged, HOST},
+        Error, NoteRecord, NoteTree,
+    };
+
+    #[test]
+    fn markdown() {
+        let mut tree = NoteTree::new();
+        tree.insert(HOST, id(1), note("buy milk", 9, &[]));
+        tree.insert(HOST, id(2), note("refactor parser\nit is slow", 10, &[]));
+        tree.insert(HOST, id(3), note("Monday\n\nQuiet day.", 11, &[1, 2, 9]));
+        tree.insert(HOST, id(4), note("call bob", 12, &[]));
+
+        assert_eq!(
+            to_markdown(&tree, &ExportOptions::default()),
+            "\
+## Monday
+
+*2024-11-09 11:00*
+
+Quiet day.
+
+- 2024-11-09 09:00 — buy milk
+- 2024-11-09 10:00 — refactor parser
+  it is slow
+- *missing note 00000000-0000-0000-0000-000000000009*
+
+- 2024-11-09 12:00 — call bob
+
+"
+        );
+
+        let options = ExportOptions {
+            since: Some("2024-11-09T11:30:00Z".parse().unwrap()),
+            ..ExportOptions::default()
+        };
+        assert_eq!(
+            to_markdown(&tree, &options),
+            "- 2024-11-09 12:00 — call bob\n\n"
+        );
+
+        let options = ExportOptions {
+            root: Some(id(2)),
+            ..ExportOptions::default()
+        };
+        assert_eq!(
+            to_markdown(&tree, &options),
+            "- 2024-11-09 10:00 — refactor parser\n  it is slow\n\n"
+        );
+    }
+
+    #[test]
+    fn jsonl() {
+        let records = [
+            NoteRecord {
+                host: HOST,
+                idx: 0,
+                id: id(1),
+                note: note("buy milk", 9, &[]),
+            },
+            NoteRecord {
+                host: HOST,
+                idx: 1,
+                id: id(2),
+                note: tagged(note("shopping\ndone", 10, &[1]), &["home"]),
+            },
+        ];
+
+        let jsonl = to_jsonl(&records);
+        assert_eq!(
+            jsonl.lines().next().unwrap(),
+            r#"{"id":"00000000-0000-0000-0000-000000000001","host":"00000000-0000-0000-0000-000000000001","idx":0,"datetime":"2024-11-09T09:00:00+00:00[UTC]","text":"buy milk","children":[],"tags":[]}"#
+        );
+
+        let notes = from_jsonl(&jsonl).unwrap();
+        assert_eq!(
+            notes,
+            records.map(|record| (record.id, record.note)).to_vec()
+        );
+
+        let err = from_jsonl("\n{}").unwrap_err();
+        assert!(matches!(err, Error::Import { line: 2, .. }), "{err:?}");
+    }
+}
@@ -0,0 +1,86 @@
+/// Hashtags, mentions and links written inline in the text of a note.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Inline {
+    /// `#tag`, without the leading `#`.
+    pub hashtags: Vec<String>,
+    /// `@name`, without the leading `@`.
+    pub mentions: Vec<String>,
+    /// `http://` and `https://` links.
+    pub urls: Vec<String>,
+}
+
+impl Inline {
+    pub fn parse(text: &str) -> Self {
+        let mut inline = Self::default();
+
+        for word in text.split_whitespace() {
+            // allow the word to be wrapped in brackets or quotes, e.g. "(#work)"
+            let word = word.trim_start_matches(['(', '[', '{', '"', '\'']);
+
+            if word.starts_with("https://") || word.starts_with("http://") {
+                let url = word.trim_end_matches(|c: char| TRAILING_PUNCTUATION.contains(&c));
+                push_unique(&mut inline.urls, url);
+            } else if let Some(tag) = word.strip_prefix('#') {
+                let tag = take_while(tag, |c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'));
+                // `#1` is more likely to be an issue number than a tag
+                if tag.chars().any(|c| !c.is_ascii_digit()) {
+                    push_unique(&mut inline.hashtags, tag);
+                }
+            } else if let Some(name) = word.strip_prefix('@') {
+                let name = take_while(name, |c| {
+                    c.is_alphanumeric() || matches!(c, '_' | '-' | '.')
+                });
+                let name = name.trim_end_matches('.');
+                if !name.is_empty() {
+                    push_unique(&mut inline.mentions, name);
+                }
+            }
+        }
+
+        inline
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashtags.is_empty() && self.mentions.is_empty() && self.urls.is_empty()
+    }
+}
+
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '}', '"', '\''];
+
+fn take_while(s: &str, f: impl Fn(char) -> bool) -> &str {
+    let end = s.find(|c| !f(c)).unwrap_or(s.len());
+    &s[..end]
+}
+
+fn push_unique(list: &mut Vec<String>, item: &str) {
+    if !list.iter().any(|i| i == item) {
+        list.push(item.to_owned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Inline;
+
+    #[test]
+    fn parse() {
+        let inline = Inline::parse(
+            "Ask @alice about #work/infra (#oncall), see https://example.com/a?b=c. \
+             Ping @bob. #1 email me@example.com #work/infra",
+        );
+
+        assert_eq!(
+            inline,
+            Inline {
+                hashtags: vec!["work/infra".to_owned(), "oncall".to_owned()],
+                mentions: vec!["alice".to_owned(), "bob".to_owned()],
+                urls: vec!["https://example.com/a?b=c".to_owned()],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert!(Inline::parse("just a # plain @ note").is_empty());
+    }
+}
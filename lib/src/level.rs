@@ -0,0 +1,113 @@
+use serde::Deserialize;
+
+use crate::NoteTree;
+
+/// Budgets for the number of unprocessed notes at each level of the merge tree.
+///
+/// Like the levels of an LSM tree, quick notes are level 0, merges of quick notes such as daily
+/// rollups are level 1, merges of those such as weekly rollups are level 2, and so on,
+/// see [`NoteTree::level`].
+/// Once a level has more unprocessed notes than its budget, they are due for review and merging.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LevelPolicy {
+    /// The budget of each level, starting at level 0. Levels without a budget are unlimited.
+    pub budgets: Vec<usize>,
+}
+
+impl Default for LevelPolicy {
+    fn default() -> Self {
+        Self {
+            budgets: vec![30, 14, 8],
+        }
+    }
+}
+
+/// The number of notes at a level of the merge tree, see [`LevelPolicy::stats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelStats {
+    pub level: usize,
+    /// All loaded notes at this level.
+    pub notes: usize,
+    /// The notes at this level that have not been merged into another note.
+    pub unprocessed: usize,
+    pub budget: Option<usize>,
+}
+
+impl LevelStats {
+    /// Whether there are more unprocessed notes at this level than its budget.
+    pub fn over_budget(&self) -> bool {
+        self.budget.is_some_and(|budget| self.unprocessed > budget)
+    }
+}
+
+impl LevelPolicy {
+    /// The notes at each level, up to the highest level that has notes or a budget.
+    pub fn stats(&self, tree: &NoteTree) -> Vec<LevelStats> {
+        let levels = tree.levels();
+        let highest = levels.values().copied().max().map_or(0, |level| level + 1);
+
+        let mut stats: Vec<_> = (0..highest.max(self.budgets.len()))
+            .map(|level| LevelStats {
+                level,
+                notes: 0,
+                unprocessed: 0,
+                budget: self.budgets.get(level).copied(),
+            })
+            .collect();
+        for level in levels.values() {
+            stats[*level].notes += 1;
+        }
+        for id in tree.unprocessed() {
+            stats[levels[&id]].unprocessed += 1;
+        }
+
+        stats
+    }
+
+    /// The levels with more unprocessed notes than their budget.
+    pub fn over_budget(&self, tree: &NoteTree) -> Vec<LevelStats> {
+        self.stats(tree)
+            .into_iter()
+            .filter(LevelStats::over_budget)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LevelPolicy, LevelStats};
+    use crate::{
+        test_util::{id, note, HOST},
+        NoteTree,
+    };
+
+    #[test]
+    fn stats() {
+        let mut tree = NoteTree::new();
+        for n in 1..=4 {
+            tree.insert(HOST, id(n), note("note", 12, &[]));
+        }
+        tree.insert(HOST, id(5), note("note", 12, &[1, 2]));
+        tree.insert(HOST, id(6), note("note", 12, &[5, 3]));
+
+        let policy = LevelPolicy {
+            budgets: vec![0, 1],
+        };
+        let stats = |level, notes, unprocessed, budget| LevelStats {
+            level,
+            notes,
+            unprocessed,
+            budget,
+        };
+        assert_eq!(
+            policy.stats(&tree),
+            [
+                stats(0, 4, 1, Some(0)),
+                stats(1, 1, 0, Some(1)),
+                stats(2, 1, 1, None),
+            ]
+        );
+        assert_eq!(policy.over_budget(&tree), [stats(0, 4, 1, Some(0))]);
+    }
+}
@@ -1,64 +1,170 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::Path,
+};
 
 use atuin_client::{
     encryption,
     record::{encryption::PASETO_V4, sqlite_store::SqliteStore, store::Store},
 };
-use atuin_common::record::{DecryptedData, Host, HostId, Record, RecordIdx};
+use atuin_common::record::{DecryptedData, EncryptedData, Host, Record, RecordIdx};
+use cache::NoteCache;
 use ciborium_io::{Read, Write};
 use ciborium_ll::{Decoder, Encoder, Header};
-use eyre::{bail, ensure, eyre, Context};
 use jiff::{
     tz::{Offset, TimeZone},
     Timestamp, Zoned,
 };
+use search::SearchIndex;
 
 pub use atuin_client::settings::Settings;
-pub use atuin_common::record::RecordId;
+pub use atuin_common::record::{HostId, RecordId};
+pub use compact::{CompactOptions, CompactSummary};
+pub use config::Config;
+pub use daily::{read_daily_notes, DailyNotes};
+pub use error::{Error, Result};
+pub use export::{from_jsonl, to_jsonl, to_markdown, ExportOptions};
+pub use inline::Inline;
+pub use level::{LevelPolicy, LevelStats};
+pub use notebook::DEFAULT_NOTEBOOK;
+pub use query::Query;
+pub use rollup::{plan_rollups, RollupPeriod};
+pub use search::SearchHit;
+pub use tree::{NoteTree, Subtree};
+
+mod cache;
+mod compact;
+mod config;
+mod daily;
+mod error;
+mod export;
+mod inline;
+mod level;
+mod notebook;
+mod query;
+mod rollup;
+mod search;
+mod sqlite;
+#[cfg(test)]
+mod test_util;
+mod tree;
 
 pub struct Client {
     store: SqliteStore,
+    search: SearchIndex,
+    cache: NoteCache,
     host_id: HostId,
     key: [u8; 32],
+    notebook: String,
+    /// The atuin record tag of the notes in `notebook`
+    tag: String,
 
+    /// The index of the next record to load, for each host
     hosts: HashMap<HostId, RecordIdx>,
+    /// Whether the notes in the cache have already been passed to `load_notes`
+    replayed: bool,
+    /// Notes added before the cache was replayed, which the caller already has
+    added: HashSet<RecordId>,
+    tags: BTreeMap<String, usize>,
+    /// The tags of each loaded note, so they can be uncounted if the note is retracted
+    note_tags: HashMap<RecordId, Vec<String>>,
+    /// Notes retracted by the tombstones loaded so far
+    retracted: HashSet<RecordId>,
 }
 
 impl Client {
-    pub async fn new(settings: &Settings) -> Self {
+    /// Open the default notebook.
+    pub async fn new(settings: &Settings) -> Result<Self> {
+        Self::with_notebook(settings, DEFAULT_NOTEBOOK).await
+    }
+
+    /// Open the named notebook. The notebook does not need to be created first.
+    pub async fn with_notebook(settings: &Settings, notebook: &str) -> Result<Self> {
+        notebook::validate(notebook)?;
+
         let store = SqliteStore::new(&settings.record_store_path, settings.local_timeout)
             .await
-            .unwrap();
-
-        let host_id = atuin_client::settings::Settings::host_id().expect("failed to get host_id");
-        let key = encryption::load_key(settings).unwrap().into();
+            .map_err(Error::store("opening record store"))?;
+
+        let host_id = Settings::host_id().ok_or(Error::HostId)?;
+        let key = encryption::load_key(settings)
+            .map_err(|err| Error::Key(err.into()))?
+            .into();
+
+        // the local indices only cover a single notebook
+        let mut dir = Path::new(&settings.record_store_path).to_owned();
+        if notebook != DEFAULT_NOTEBOOK {
+            dir = dir
+                .with_file_name("notebooks")
+                .join(notebook)
+                .join("store.db");
+        }
+        let search_path = dir.with_file_name("search.db");
+        let cache_path = dir.with_file_name("cache.db");
 
-        Self {
+        Self::open(
             store,
             host_id,
             key,
-            hosts: HashMap::new(),
-        }
+            notebook,
+            search_path.to_str().expect("derived from a UTF-8 path"),
+            cache_path.to_str().expect("derived from a UTF-8 path"),
+            settings.local_timeout,
+        )
+        .await
     }
 
-    pub fn test(store: SqliteStore, host_id: HostId) -> Self {
+    pub async fn test(store: SqliteStore, host_id: HostId) -> Self {
         let key = [0x55; 32];
 
-        Self {
+        Self::open(
             store,
             host_id,
             key,
-            hosts: HashMap::new(),
-        }
+            DEFAULT_NOTEBOOK,
+            ":memory:",
+            ":memory:",
+            1.0,
+        )
+        .await
+        .expect("in-memory indices should open")
     }
 
-    pub async fn add_record(
-        &mut self,
-        note: String,
-        children: Vec<RecordId>,
-        datetime: Zoned,
-    ) -> RecordId {
-        let timestamp = datetime.timestamp().duration_since(Timestamp::UNIX_EPOCH);
+    async fn open(
+        store: SqliteStore,
+        host_id: HostId,
+        key: [u8; 32],
+        notebook: &str,
+        search_path: &str,
+        cache_path: &str,
+        timeout: f64,
+    ) -> Result<Self> {
+        let search = SearchIndex::new(search_path, timeout).await?;
+        let cache = NoteCache::new(cache_path, timeout).await?;
+        let hosts = cache.hosts().await?;
+
+        Ok(Self {
+            store,
+            search,
+            cache,
+            host_id,
+            key,
+            notebook: notebook.to_owned(),
+            tag: notebook::record_tag(notebook),
+            hosts,
+            replayed: false,
+            added: HashSet::new(),
+            tags: BTreeMap::new(),
+            note_tags: HashMap::new(),
+            retracted: HashSet::new(),
+        })
+    }
+
+    pub async fn add_record(&mut self, note: Note) -> Result<RecordId> {
+        let timestamp = note
+            .datetime
+            .timestamp()
+            .duration_since(Timestamp::UNIX_EPOCH);
         let timestamp = uuid::Timestamp::from_unix(
             uuid::NoContext,
             timestamp.as_secs() as u64,
@@ -66,112 +172,738 @@ impl Client {
         );
         let id = RecordId(uuid::Uuid::new_v7(timestamp));
 
+        self.add_record_with_id(id, note).await?;
+        Ok(id)
+    }
+
+    /// Add a note with a known id, e.g. when restoring it from a backup.
+    ///
+    /// The caller must make sure that the id is not already used.
+    pub async fn add_record_with_id(&mut self, id: RecordId, note: Note) -> Result<()> {
         let idx = self
             .store
-            .last(self.host_id, Note::TAG)
+            .last(self.host_id, &self.tag)
             .await
-            .unwrap()
+            .map_err(Error::store("loading last record index"))?
             .map_or(0, |p| p.idx + 1);
 
-        let note = Note {
-            note,
-            children,
-            datetime,
-        };
-
         let record = Record::builder()
             .id(id)
-            .data(DecryptedData(note.ser_v0_to_vec()))
-            .tag(Note::TAG.to_string())
+            .data(DecryptedData(note.ser_v1_to_vec()))
+            .tag(self.tag.clone())
             .idx(idx)
             .host(Host::new(self.host_id))
-            .version(Note::VERSION.to_string())
+            .version(note.version().to_string())
             .build();
         let record = record.encrypt::<PASETO_V4>(&self.key);
-        self.store.push(&record).await.unwrap();
+        self.store
+            .push(&record)
+            .await
+            .map_err(Error::store("pushing record to store"))?;
+
+        // If we have not yet loaded all of our own earlier records, leave this one for
+        // `load_notes` to pick up in order, so that the note cache never moves past a record
+        // it does not hold. Another client using the same host may have added those records.
+        let next_idx = self.hosts.entry(self.host_id).or_default();
+        if *next_idx == idx {
+            *next_idx = idx + 1;
+            if !self.replayed {
+                self.added.insert(id);
+            }
+            let notes = self.index(vec![(idx, id, note)]).await?;
+            let cached: Vec<_> = notes
+                .iter()
+                .map(|(idx, id, note)| (*idx, *id, note))
+                .collect();
+            self.cache
+                .insert(self.host_id, &cached, &[], Some(idx + 1))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Retract notes, hiding them from [`Client::load_notes`], search and exports.
+    ///
+    /// The record store is append-only, so this adds a tombstone note instead of removing the notes.
+    /// The retracted records are still kept, encrypted, in the record store and on the sync server.
+    pub async fn retract(&mut self, ids: &[RecordId]) -> Result<RecordId> {
+        self.add_record(Note {
+            note: String::new(),
+            datetime: Zoned::now(),
+            children: vec![],
+            tags: vec![],
+            retracts: ids.to_vec(),
+        })
+        .await
+    }
+
+    /// Every note record in the store for this notebook, from all hosts.
+    ///
+    /// Unlike [`Client::load_notes`] this always reads the whole store, and does not
+    /// update the local indices. Records that cannot be read are left out,
+    /// as are tombstones and the notes they retract.
+    pub async fn records(&self) -> Result<Vec<NoteRecord>> {
+        let mut records = self.all_records().await?;
+        let retracted: HashSet<_> = records
+            .iter()
+            .flat_map(|record| record.note.retracts.iter().copied())
+            .collect();
+        records.retain(|record| !record.note.is_tombstone() && !retracted.contains(&record.id));
+        Ok(records)
+    }
+
+    /// Like [`Client::records`], but including tombstones and the notes they retract.
+    async fn all_records(&self) -> Result<Vec<NoteRecord>> {
+        let mut records = vec![];
+        self.for_each_record(
+            |tag| tag == self.tag,
+            |record| {
+                let (host, idx, id) = (record.host.id, record.idx, record.id);
+                if let Ok(note) = Note::decrypt(record, &self.key) {
+                    records.push(NoteRecord {
+                        host,
+                        idx,
+                        id,
+                        note,
+                    });
+                }
+            },
+        )
+        .await?;
+        Ok(records)
+    }
+
+    /// Add notes exported by [`to_jsonl`], keeping their original ids.
+    ///
+    /// Notes whose ids are already in the store are skipped, even if they are in another notebook.
+    /// Notes that were retracted, or deleted by [`Client::compact`], are skipped too,
+    /// so that importing an old backup does not bring them back.
+    /// Returns the ids that were added.
+    pub async fn import(&mut self, notes: Vec<(RecordId, Note)>) -> Result<Vec<RecordId>> {
+        // record ids are unique across the whole store, not just this notebook,
+        // so look them up by id rather than reading every record
+        let mut existing = HashSet::new();
+        for (id, _) in &notes {
+            if self.store.get(*id).await.is_ok() {
+                existing.insert(*id);
+            }
+        }
+
+        let mut retracted = HashSet::new();
+        let mut merged = HashSet::new();
+        for record in self.all_records().await? {
+            retracted.extend(record.note.retracts);
+            merged.extend(record.note.children);
+        }
+        // compaction deletes notes that were merged into a note that is kept,
+        // along with any notes merged into those
+        let mut compacted = HashSet::new();
+        loop {
+            let found = compacted.len();
+            for (id, note) in &notes {
+                if !existing.contains(id) && merged.contains(id) && compacted.insert(*id) {
+                    merged.extend(note.children.iter().copied());
+                }
+            }
+            if compacted.len() == found {
+                break;
+            }
+        }
+
+        let mut imported = vec![];
+        for (id, note) in notes {
+            if retracted.contains(&id) || compacted.contains(&id) {
+                continue;
+            }
+            if existing.insert(id) {
+                self.add_record_with_id(id, note).await?;
+                imported.push(id);
+            }
+        }
+        Ok(imported)
+    }
+
+    /// Pass every record in the store with a tag matching `filter` to `f`.
+    async fn for_each_record(
+        &self,
+        filter: impl Fn(&str) -> bool,
+        mut f: impl FnMut(Record<EncryptedData>),
+    ) -> Result<()> {
+        let status = self
+            .store
+            .status()
+            .await
+            .map_err(Error::store("loading current status"))?;
+
+        for (host_id, tags) in status.hosts {
+            for tag in tags.keys().filter(|tag| filter(tag)) {
+                let mut next_idx = 0;
+                loop {
+                    let batch = self
+                        .store
+                        .next(host_id, tag, next_idx, 100)
+                        .await
+                        .map_err(Error::store("loading next batch of records"))?;
+                    if batch.is_empty() {
+                        break;
+                    }
+                    for record in batch {
+                        next_idx = record.idx + 1;
+                        f(record);
+                    }
+                }
+            }
+        }
 
-        *self.hosts.entry(self.host_id).or_default() = idx + 1;
+        Ok(())
+    }
+
+    /// The atuin host id that new notes are written from.
+    pub fn host_id(&self) -> HostId {
+        self.host_id
+    }
+
+    /// The notebook this client reads and writes notes in.
+    pub fn notebook(&self) -> &str {
+        &self.notebook
+    }
+
+    /// All notebooks that have been created or contain notes, including the default notebook.
+    ///
+    /// Only notebooks that have been synced to the local store are known.
+    pub async fn notebooks(&self) -> Result<Vec<String>> {
+        let mut notebooks = BTreeSet::from([DEFAULT_NOTEBOOK.to_owned()]);
+
+        let status = self
+            .store
+            .status()
+            .await
+            .map_err(Error::store("loading current status"))?;
+        for tags in status.hosts.values() {
+            notebooks.extend(
+                tags.keys()
+                    .filter_map(|tag| notebook::from_record_tag(tag))
+                    .map(str::to_owned),
+            );
+        }
+
+        let records = self
+            .store
+            .all_tagged(notebook::NOTEBOOK_TAG)
+            .await
+            .map_err(Error::store("loading notebooks"))?;
+        for record in records {
+            // a notebook we cannot read will still be listed once it has notes in it
+            if let Ok(name) = notebook::decrypt(record, &self.key) {
+                notebooks.insert(name);
+            }
+        }
+
+        Ok(notebooks.into_iter().collect())
+    }
+
+    /// Create a notebook, so that it is listed by [`Client::notebooks`] before it has any notes.
+    pub async fn create_notebook(&mut self, name: &str) -> Result<()> {
+        notebook::validate(name)?;
+        if self.notebooks().await?.iter().any(|n| n == name) {
+            return Ok(());
+        }
+
+        let idx = self
+            .store
+            .last(self.host_id, notebook::NOTEBOOK_TAG)
+            .await
+            .map_err(Error::store("loading last record index"))?
+            .map_or(0, |p| p.idx + 1);
+
+        let record = Record::builder()
+            .data(DecryptedData(name.as_bytes().to_vec()))
+            .tag(notebook::NOTEBOOK_TAG.to_string())
+            .idx(idx)
+            .host(Host::new(self.host_id))
+            .version(notebook::NOTEBOOK_VERSION.to_string())
+            .build()
+            .encrypt::<PASETO_V4>(&self.key);
+        self.store
+            .push(&record)
+            .await
+            .map_err(Error::store("pushing record to store"))
+    }
+
+    /// Delete the records of notes that were merged into a note written at least
+    /// `options.min_age` ago.
+    ///
+    /// The merge note summarises the notes merged into it, so they no longer need to be kept
+    /// and decrypted on every load. From then on they are shown as missing.
+    /// The sync server keeps its copy of the records, and other hosts keep theirs unless
+    /// `options.propagate` is set.
+    pub async fn compact(&mut self, options: &CompactOptions) -> Result<CompactSummary> {
+        // a negative age would compact merges written in the future
+        if options.min_age.is_negative() {
+            return Err(Error::NegativeAge);
+        }
+
+        let records = self.records().await?;
+        let mut tree = NoteTree::new();
+        for record in &records {
+            tree.insert(record.host, record.id, record.note.clone());
+        }
+
+        let cutoff = Zoned::now().saturating_sub(options.min_age).timestamp();
+        let (merges, delete) = compact::compactable(&tree, cutoff);
+        let delete = records
+            .iter()
+            .filter(|record| delete.contains(&record.id))
+            .map(|record| (record.host, record.idx, record.id))
+            .collect();
+
+        if options.dry_run {
+            let deleted = self.deletable(delete).await?;
+            let deleted = deleted.into_iter().map(|(_, _, id)| id).collect();
+            return Ok(CompactSummary { merges, deleted });
+        }
+        let deleted = self.delete_records(delete).await?;
+
+        if options.propagate && !deleted.is_empty() {
+            let idx = self
+                .store
+                .last(self.host_id, compact::COMPACTION_TAG)
+                .await
+                .map_err(Error::store("loading last record index"))?
+                .map_or(0, |p| p.idx + 1);
+
+            let record = Record::builder()
+                .data(DecryptedData(compact::encode(&deleted)))
+                .tag(compact::COMPACTION_TAG.to_string())
+                .idx(idx)
+                .host(Host::new(self.host_id))
+                .version(compact::COMPACTION_VERSION.to_string())
+                .build()
+                .encrypt::<PASETO_V4>(&self.key);
+            self.store
+                .push(&record)
+                .await
+                .map_err(Error::store("pushing record to store"))?;
+        }
+
+        Ok(CompactSummary { merges, deleted })
+    }
+
+    /// Query the notes loaded into this notebook's note cache, see [`Query::fetch`].
+    ///
+    /// Unlike [`NoteTree::query`], this does not need the notes to be kept in a tree.
+    pub fn query(&self) -> Query<&Self> {
+        Query::new(self)
+    }
+
+    /// Search the text and tags of all loaded notes, best matches first.
+    pub async fn search(&self, query: &str, limit: u32) -> Result<Vec<SearchHit>> {
+        self.search.search(query, limit).await
+    }
 
-        id
+    /// All tags on notes added or loaded by this client, with the number of notes using each.
+    pub fn tags(&self) -> &BTreeMap<String, usize> {
+        &self.tags
     }
 
-    pub async fn sync(&mut self, settings: &Settings) {
-        atuin_client::record::sync::sync(settings, &self.store).await.unwrap();
+    /// Sync the record store with the atuin server.
+    ///
+    /// Notes deleted by [`Client::compact`] on other hosts are deleted from the local store too.
+    pub async fn sync(&mut self, settings: &Settings) -> Result<SyncSummary> {
+        let (uploaded, downloaded) = atuin_client::record::sync::sync(settings, &self.store)
+            .await
+            .map_err(Error::Sync)?;
+        self.apply_compactions().await?;
+        Ok(SyncSummary {
+            uploaded: uploaded as u64,
+            downloaded: downloaded.len() as u64,
+        })
     }
 
+    /// Load all notes that have not yet been loaded by this client, passing each to `f`.
+    ///
+    /// The first call also passes all notes from the local note cache, so that only records
+    /// added since the cache was last updated need to be decrypted.
+    ///
+    /// Tombstones are passed to `f` too, so that notes retracted after they were passed to `f`
+    /// can be removed, as [`NoteTree::insert`] does. Notes that were already retracted are not passed.
+    ///
+    /// Records that cannot be read, e.g. because they were encrypted with a different key
+    /// or written in a newer format, are not passed to `f` but are returned instead.
+    /// They are retried the next time a client is opened.
     pub async fn load_notes(
         &mut self,
         mut f: impl FnMut(HostId, RecordId, Note),
-    ) -> eyre::Result<()> {
+    ) -> Result<Vec<SkippedRecord>> {
+        let mut skipped = vec![];
+
+        if !self.replayed {
+            self.replay(&mut f, &mut skipped).await?;
+            self.replayed = true;
+        }
+
         let status = self
             .store
             .status()
             .await
-            .context("loading current status")?;
+            .map_err(Error::store("loading current status"))?;
 
         for (host_id, tags) in status.hosts {
-            let Some(&last_idx) = tags.get(Note::TAG) else {
+            let Some(&last_idx) = tags.get(&self.tag) else {
                 continue;
             };
-            let host_entry = self.hosts.entry(host_id).or_insert(0);
-            if last_idx < *host_entry {
+            let mut next_idx = self.hosts.get(&host_id).copied().unwrap_or(0);
+            if last_idx < next_idx {
                 continue;
             }
 
             loop {
                 let batch = self
                     .store
-                    .next(host_id, Note::TAG, *host_entry, 100)
-                    .await?;
+                    .next(host_id, &self.tag, next_idx, 100)
+                    .await
+                    .map_err(Error::store("loading next batch of records"))?;
                 if batch.is_empty() {
                     break;
                 }
 
+                let mut notes = Vec::with_capacity(batch.len());
+                let mut skipped_idx = vec![];
                 for note_record in batch {
-                    *host_entry = note_record.idx + 1;
+                    next_idx = note_record.idx + 1;
+
+                    let (idx, id) = (note_record.idx, note_record.id);
+                    match Note::decrypt(note_record, &self.key) {
+                        Ok(note) => notes.push((idx, id, note)),
+                        Err(reason) => {
+                            skipped_idx.push(idx);
+                            skipped.push(SkippedRecord {
+                                host: host_id,
+                                idx,
+                                id,
+                                reason,
+                            });
+                        }
+                    }
+                }
 
-                    if note_record.version != "v0" {
-                        continue;
+                self.hosts.insert(host_id, next_idx);
+                self.loaded(host_id, notes, &skipped_idx, Some(next_idx), &mut f)
+                    .await?;
+            }
+        }
+
+        Ok(skipped)
+    }
+
+    /// Pass all cached notes to `f`, and retry any records that previously failed to load.
+    async fn replay(
+        &mut self,
+        f: &mut impl FnMut(HostId, RecordId, Note),
+        skipped: &mut Vec<SkippedRecord>,
+    ) -> Result<()> {
+        let mut notes = self.cache.notes().await?;
+        notes.retain(|(_, id, _)| !self.added.contains(id));
+        self.added.clear();
+
+        // the search index might have been removed independently of the cache
+        let notes = self.index(notes).await?;
+        for (host, id, note) in notes {
+            f(host, id, note);
+        }
+
+        for (host, idx) in self.cache.skipped().await? {
+            let record = self
+                .store
+                .idx(host, &self.tag, idx)
+                .await
+                .map_err(Error::store("loading skipped record"))?;
+            let Some(record) = record else {
+                continue;
+            };
+
+            let id = record.id;
+            match Note::decrypt(record, &self.key) {
+                Ok(note) => {
+                    self.loaded(host, vec![(idx, id, note)], &[], None, f)
+                        .await?
+                }
+                Err(reason) => skipped.push(SkippedRecord {
+                    host,
+                    idx,
+                    id,
+                    reason,
+                }),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record newly loaded notes in the cache and search index, then pass them to `f`.
+    async fn loaded(
+        &mut self,
+        host: HostId,
+        notes: Vec<(RecordIdx, RecordId, Note)>,
+        skipped: &[RecordIdx],
+        next_idx: Option<RecordIdx>,
+        f: &mut impl FnMut(HostId, RecordId, Note),
+    ) -> Result<()> {
+        let notes = self.index(notes).await?;
+
+        let cached: Vec<_> = notes
+            .iter()
+            .map(|(idx, id, note)| (*idx, *id, note))
+            .collect();
+        self.cache.insert(host, &cached, skipped, next_idx).await?;
+
+        for (_, id, note) in notes {
+            f(host, id, note);
+        }
+
+        Ok(())
+    }
+
+    /// Add newly loaded notes to the search index and tag counts, applying any tombstones.
+    ///
+    /// Returns the notes that have not been retracted, including the tombstones.
+    async fn index<T>(
+        &mut self,
+        notes: Vec<(T, RecordId, Note)>,
+    ) -> Result<Vec<(T, RecordId, Note)>> {
+        let mut retracted = vec![];
+        for (_, _, note) in &notes {
+            for &id in &note.retracts {
+                if self.retracted.insert(id) {
+                    retracted.push(id);
+                }
+            }
+        }
+        let notes: Vec<_> = notes
+            .into_iter()
+            .filter(|(_, id, _)| !self.retracted.contains(id))
+            .collect();
+
+        let indexed: Vec<_> = notes
+            .iter()
+            .filter(|(_, _, note)| !note.is_tombstone())
+            .map(|(_, id, note)| (*id, note.clone()))
+            .collect();
+        self.search.insert(&indexed).await?;
+
+        for (_, id, note) in &notes {
+            if note.tags.is_empty() {
+                continue;
+            }
+            for tag in &note.tags {
+                *self.tags.entry(tag.clone()).or_default() += 1;
+            }
+            self.note_tags.insert(*id, note.tags.clone());
+        }
+        self.forget(&retracted).await?;
+
+        Ok(notes)
+    }
+
+    /// Remove notes from the local indices and tag counts.
+    async fn forget(&mut self, ids: &[RecordId]) -> Result<()> {
+        self.search.remove(ids).await?;
+        // don't keep the text of removed notes around in plain text
+        self.cache.remove(ids).await?;
+
+        for id in ids {
+            for tag in self.note_tags.remove(id).unwrap_or_default() {
+                if let Some(count) = self.tags.get_mut(&tag) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.tags.remove(&tag);
                     }
-                    let Ok(note_record) = note_record.decrypt::<PASETO_V4>(&self.key) else {
-                        continue;
-                    };
-                    let note = Note::deser_v0(&note_record.data.0)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delete note records from the local store and indices, returning the ids that were deleted.
+    async fn delete_records(
+        &mut self,
+        records: Vec<(HostId, RecordIdx, RecordId)>,
+    ) -> Result<Vec<RecordId>> {
+        let mut deleted = vec![];
+        for (_, _, id) in self.deletable(records).await? {
+            self.store
+                .delete(id)
+                .await
+                .map_err(Error::store("deleting record"))?;
+            deleted.push(id);
+        }
+
+        self.forget(&deleted).await?;
+        Ok(deleted)
+    }
+
+    /// The records that [`Client::delete_records`] would delete.
+    async fn deletable(
+        &self,
+        records: Vec<(HostId, RecordIdx, RecordId)>,
+    ) -> Result<Vec<(HostId, RecordIdx, RecordId)>> {
+        let status = self
+            .store
+            .status()
+            .await
+            .map_err(Error::store("loading current status"))?;
+
+        // sync compares the last index of each host, so deleting the last record
+        // would make it look like it still needs to be downloaded
+        Ok(records
+            .into_iter()
+            .filter(|(host, idx, _)| {
+                let last = status.hosts.get(host).and_then(|tags| tags.get(&self.tag));
+                last != Some(idx)
+            })
+            .collect())
+    }
+
+    /// Delete the records of notes deleted by [`Client::compact`] on other hosts.
+    async fn apply_compactions(&mut self) -> Result<()> {
+        let compactions = self
+            .store
+            .all_tagged(compact::COMPACTION_TAG)
+            .await
+            .map_err(Error::store("loading compactions"))?;
 
-                    f(note_record.host.id, note_record.id, note);
+        let mut records = vec![];
+        for compaction in compactions {
+            // compactions written with another key or a newer version are ignored,
+            // the notes are just kept a little longer
+            let Ok(ids) = compact::decrypt(compaction, &self.key) else {
+                continue;
+            };
+            for id in ids {
+                // the record was already deleted, or is in another notebook
+                let Ok(record) = self.store.get(id).await else {
+                    continue;
+                };
+                if record.tag == self.tag {
+                    records.push((record.host.id, record.idx, id));
                 }
             }
         }
 
+        self.delete_records(records).await?;
         Ok(())
     }
 }
 
-#[derive(PartialEq, Debug)]
+/// The number of records exchanged with the atuin server by [`Client::sync`].
+///
+/// This includes records of all tags in the store, not just notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncSummary {
+    pub uploaded: u64,
+    pub downloaded: u64,
+}
+
+/// A note as stored in the record store, see [`Client::records`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteRecord {
+    pub host: HostId,
+    pub idx: RecordIdx,
+    pub id: RecordId,
+    pub note: Note,
+}
+
+/// A note record that could not be loaded.
+#[derive(Debug)]
+pub struct SkippedRecord {
+    pub host: HostId,
+    pub idx: RecordIdx,
+    pub id: RecordId,
+    pub reason: Error,
+}
+
+#[derive(PartialEq, Clone, Debug)]
 pub struct Note {
     pub note: String,
     pub datetime: Zoned,
     pub children: Vec<RecordId>,
+    pub tags: Vec<String>,
+    /// Notes this note retracts, see [`Client::retract`].
+    ///
+    /// A note that retracts others is a tombstone: neither it nor the notes it retracts are shown.
+    pub retracts: Vec<RecordId>,
 }
 
 /// <https://github.com/lucas-clemente/cbor-specs/blob/master/uuid.md>
 const UUID_BYTES_TAG: u64 = 37;
 
+// v1 map keys
+const NOTE_KEY: u64 = 0;
+const DATETIME_KEY: u64 = 1;
+const CHILDREN_KEY: u64 = 2;
+const TAGS_KEY: u64 = 3;
+const RETRACTS_KEY: u64 = 4;
+
 impl Note {
     const TAG: &str = "note_lsm::note";
-    const VERSION: &str = "v0";
+    const VERSION: &str = "v1";
+    /// Tombstones use the v1 encoding under a new version, so that older clients
+    /// skip them instead of showing them as empty notes next to the notes they retract.
+    const TOMBSTONE_VERSION: &str = "v2";
+
+    /// The record version to write this note with.
+    fn version(&self) -> &'static str {
+        if self.is_tombstone() {
+            Self::TOMBSTONE_VERSION
+        } else {
+            Self::VERSION
+        }
+    }
 
-    fn ser_v0_to_vec(&self) -> Vec<u8> {
-        let mut buf = vec![];
-        self.ser_v0(&mut buf);
-        buf
+    /// Whether this note only exists to retract other notes.
+    pub fn is_tombstone(&self) -> bool {
+        !self.retracts.is_empty()
+    }
+
+    /// The hashtags, mentions and links written in the note text.
+    pub fn inline(&self) -> Inline {
+        Inline::parse(&self.note)
     }
 
-    fn ser_v0(&self, buf: &mut Vec<u8>) {
-        self.ser_v0_inner(buf)
+    fn decrypt(record: Record<EncryptedData>, key: &[u8; 32]) -> Result<Self> {
+        let id = record.id;
+        let deser = match record.version.as_str() {
+            "v0" => Self::deser_v0,
+            "v1" | "v2" => Self::deser_v1,
+            _ => {
+                return Err(Error::UnknownVersion {
+                    id,
+                    version: record.version,
+                })
+            }
+        };
+
+        let record = record
+            .decrypt::<PASETO_V4>(key)
+            .map_err(|err| Error::Decrypt {
+                id,
+                source: err.into(),
+            })?;
+
+        deser(&record.data.0)
+    }
+
+    #[cfg(test)]
+    fn ser_v0_to_vec(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.ser_v0_inner(&mut buf)
             .expect("encoding to vec should not fail");
+        buf
     }
 
+    #[cfg(test)]
     fn ser_v0_inner(&self, buf: &mut Vec<u8>) -> Result<(), std::io::Error> {
         let mut enc = Encoder::from(buf);
         enc.push(ciborium_ll::Header::Array(Some(3)))?;
@@ -183,82 +915,282 @@ impl Note {
         zoned_cbor_9581_ser(&self.datetime, &mut enc)?;
 
         // children
-        enc.push(ciborium_ll::Header::Array(Some(self.children.len())))?;
-        for child in &self.children {
-            enc.push(ciborium_ll::Header::Tag(UUID_BYTES_TAG))?;
-            enc.bytes(child.0.as_bytes(), None)?;
-        }
+        children_ser(&self.children, &mut enc)?;
         Ok(())
     }
 
-    fn deser_v0(b: &[u8]) -> eyre::Result<Self> {
-        let mut scratch = [0; 1024];
+    fn deser_v0(b: &[u8]) -> Result<Self> {
         let mut dec = Decoder::from(b);
 
-        match dec.pull().map_err(map_deser_err)? {
+        match pull(&mut dec)? {
             ciborium_ll::Header::Array(Some(3)) => {}
-            header => bail!("invalid header: {header:?}"),
+            header => return Err(invalid_header(&mut dec, header)),
         };
 
-        let note_len = match dec.pull().map_err(map_deser_err)? {
-            ciborium_ll::Header::Text(len) => len,
-            header => bail!("invalid header: {header:?}"),
-        };
+        let note = text_deser(&mut dec)?;
+        let datetime = zoned_cbor_9581_deser(&mut dec)?;
+        let children = children_deser(&mut dec)?;
 
-        let note = {
-            let mut segments = dec.text(note_len);
-            let mut note = String::with_capacity(note_len.unwrap_or_default());
+        Ok(Self {
+            note,
+            children,
+            datetime,
+            tags: vec![],
+            retracts: vec![],
+        })
+    }
+
+    fn ser_v1_to_vec(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.ser_v1_inner(&mut buf)
+            .expect("encoding to vec should not fail");
+        buf
+    }
+
+    /// v1 encodes the note as a map with integer keys, so that new fields can be added
+    /// without breaking older readers, which skip any keys they do not know.
+    fn ser_v1_inner(&self, buf: &mut Vec<u8>) -> Result<(), std::io::Error> {
+        let mut enc = Encoder::from(buf);
+
+        let len = 2
+            + usize::from(!self.children.is_empty())
+            + usize::from(!self.tags.is_empty())
+            + usize::from(!self.retracts.is_empty());
+        enc.push(ciborium_ll::Header::Map(Some(len)))?;
+
+        enc.push(ciborium_ll::Header::Positive(NOTE_KEY))?;
+        enc.text(&self.note, None)?;
+
+        enc.push(ciborium_ll::Header::Positive(DATETIME_KEY))?;
+        zoned_cbor_9581_ser(&self.datetime, &mut enc)?;
+
+        if !self.children.is_empty() {
+            enc.push(ciborium_ll::Header::Positive(CHILDREN_KEY))?;
+            children_ser(&self.children, &mut enc)?;
+        }
+
+        if !self.tags.is_empty() {
+            enc.push(ciborium_ll::Header::Positive(TAGS_KEY))?;
+            enc.push(ciborium_ll::Header::Array(Some(self.tags.len())))?;
+            for tag in &self.tags {
+                enc.text(tag, None)?;
+            }
+        }
 
-            while let Some(mut segment) = segments.pull().map_err(map_deser_err)? {
-                while let Some(chunk) = segment.pull(&mut scratch).map_err(map_deser_err)? {
-                    note.push_str(chunk);
+        if !self.retracts.is_empty() {
+            enc.push(ciborium_ll::Header::Positive(RETRACTS_KEY))?;
+            children_ser(&self.retracts, &mut enc)?;
+        }
+
+        Ok(())
+    }
+
+    fn deser_v1(b: &[u8]) -> Result<Self> {
+        let mut dec = Decoder::from(b);
+
+        let len = match pull(&mut dec)? {
+            ciborium_ll::Header::Map(Some(len)) => len,
+            header => return Err(invalid_header(&mut dec, header)),
+        };
+
+        let mut note = None;
+        let mut datetime = None;
+        let mut children = vec![];
+        let mut tags = vec![];
+        let mut retracts = vec![];
+
+        for _ in 0..len {
+            match pull(&mut dec)? {
+                ciborium_ll::Header::Positive(NOTE_KEY) => note = Some(text_deser(&mut dec)?),
+                ciborium_ll::Header::Positive(DATETIME_KEY) => {
+                    datetime = Some(zoned_cbor_9581_deser(&mut dec)?)
                 }
+                ciborium_ll::Header::Positive(CHILDREN_KEY) => children = children_deser(&mut dec)?,
+                ciborium_ll::Header::Positive(TAGS_KEY) => tags = tags_deser(&mut dec)?,
+                ciborium_ll::Header::Positive(RETRACTS_KEY) => retracts = children_deser(&mut dec)?,
+                // unknown key, likely written by a newer client
+                ciborium_ll::Header::Positive(_) => skip_value(&mut dec)?,
+                header => return Err(invalid_header(&mut dec, header)),
             }
+        }
 
-            note
+        let Some(note) = note else {
+            return Err(decode_err(dec.offset(), "missing note text"));
+        };
+        let Some(datetime) = datetime else {
+            return Err(decode_err(dec.offset(), "missing note datetime"));
         };
 
-        let datetime = zoned_cbor_9581_deser(&mut dec)?;
+        Ok(Self {
+            note,
+            children,
+            datetime,
+            tags,
+            retracts,
+        })
+    }
+}
+
+fn text_deser<E: std::fmt::Display>(dec: &mut Decoder<impl Read<Error = E>>) -> Result<String> {
+    let len = match pull(dec)? {
+        ciborium_ll::Header::Text(len) => len,
+        header => return Err(invalid_header(dec, header)),
+    };
+
+    let mut scratch = [0; 1024];
+    let offset = dec.offset();
+    let mut segments = dec.text(len);
+    let mut text = String::with_capacity(len.unwrap_or_default());
+
+    while let Some(mut segment) = segments.pull().map_err(map_deser_err(offset))? {
+        while let Some(chunk) = segment.pull(&mut scratch).map_err(map_deser_err(offset))? {
+            text.push_str(chunk);
+        }
+    }
+
+    Ok(text)
+}
 
-        let children_len = match dec.pull().map_err(map_deser_err)? {
-            ciborium_ll::Header::Array(Some(len)) => len,
-            header => bail!("invalid header: {header:?}"),
+fn children_ser<E>(
+    children: &[RecordId],
+    enc: &mut Encoder<impl Write<Error = E>>,
+) -> Result<(), E> {
+    enc.push(ciborium_ll::Header::Array(Some(children.len())))?;
+    for child in children {
+        enc.push(ciborium_ll::Header::Tag(UUID_BYTES_TAG))?;
+        enc.bytes(child.0.as_bytes(), None)?;
+    }
+    Ok(())
+}
+
+fn children_deser<E: std::fmt::Display>(
+    dec: &mut Decoder<impl Read<Error = E>>,
+) -> Result<Vec<RecordId>> {
+    let children_len = match pull(dec)? {
+        ciborium_ll::Header::Array(Some(len)) => len,
+        header => return Err(invalid_header(dec, header)),
+    };
+
+    let mut children = Vec::with_capacity(children_len);
+    for _ in 0..children_len {
+        match pull(dec)? {
+            ciborium_ll::Header::Tag(UUID_BYTES_TAG) => {}
+            header => return Err(invalid_header(dec, header)),
         };
+        match pull(dec)? {
+            ciborium_ll::Header::Bytes(Some(16)) => {}
+            header => return Err(invalid_header(dec, header)),
+        };
+
+        let offset = dec.offset();
+        let mut segments = dec.bytes(Some(16));
+        let mut id = [0; 16];
+        let Some(mut segment) = segments.pull().map_err(map_deser_err(offset))? else {
+            return Err(decode_err(offset, "expecting 1 segment for child id"));
+        };
+        if segment.left() != 16 {
+            return Err(decode_err(offset, "expecting 16 bytes for child id"));
+        }
+        segment.pull(&mut id[..]).map_err(map_deser_err(offset))?;
+
+        children.push(RecordId(uuid::Uuid::from_bytes(id)));
+    }
+
+    Ok(children)
+}
+
+fn tags_deser<E: std::fmt::Display>(
+    dec: &mut Decoder<impl Read<Error = E>>,
+) -> Result<Vec<String>> {
+    let tags_len = match pull(dec)? {
+        ciborium_ll::Header::Array(Some(len)) => len,
+        header => return Err(invalid_header(dec, header)),
+    };
+
+    let mut tags = Vec::with_capacity(tags_len);
+    for _ in 0..tags_len {
+        tags.push(text_deser(dec)?);
+    }
+
+    Ok(tags)
+}
+
+/// Skip over the next data item, including any nested items.
+fn skip_value<E: std::fmt::Display>(dec: &mut Decoder<impl Read<Error = E>>) -> Result<()> {
+    let mut scratch = [0; 1024];
+
+    // number of data items left to skip
+    let mut items = 1usize;
+
+    while items > 0 {
+        items -= 1;
+
+        let header = pull(dec)?;
+        match header {
+            ciborium_ll::Header::Positive(_)
+            | ciborium_ll::Header::Negative(_)
+            | ciborium_ll::Header::Float(_)
+            | ciborium_ll::Header::Simple(_) => {}
+            ciborium_ll::Header::Tag(_) => items += 1,
+            ciborium_ll::Header::Array(Some(len)) => items += len,
+            ciborium_ll::Header::Map(Some(len)) => items += len * 2,
+            ciborium_ll::Header::Array(None) | ciborium_ll::Header::Map(None) => {
+                return Err(decode_err(
+                    dec.offset(),
+                    "indefinite length containers are not supported",
+                ))
+            }
+            ciborium_ll::Header::Bytes(len) => {
+                let offset = dec.offset();
+                let mut segments = dec.bytes(len);
+                while let Some(mut segment) = segments.pull().map_err(map_deser_err(offset))? {
+                    while segment
+                        .pull(&mut scratch)
+                        .map_err(map_deser_err(offset))?
+                        .is_some()
+                    {}
+                }
+            }
+            ciborium_ll::Header::Text(len) => {
+                let offset = dec.offset();
+                let mut segments = dec.text(len);
+                while let Some(mut segment) = segments.pull().map_err(map_deser_err(offset))? {
+                    while segment
+                        .pull(&mut scratch)
+                        .map_err(map_deser_err(offset))?
+                        .is_some()
+                    {}
+                }
+            }
+            ciborium_ll::Header::Break => return Err(invalid_header(dec, header)),
+        }
+    }
 
-        let mut children = Vec::with_capacity(children_len);
-        for _ in 0..children_len {
-            match dec.pull().map_err(map_deser_err)? {
-                ciborium_ll::Header::Tag(UUID_BYTES_TAG) => {}
-                header => bail!("invalid header: {header:?}"),
-            };
-            match dec.pull().map_err(map_deser_err)? {
-                ciborium_ll::Header::Bytes(Some(16)) => {}
-                header => bail!("invalid header: {header:?}"),
-            };
+    Ok(())
+}
 
-            let mut segments = dec.bytes(Some(16));
-            let mut id = [0; 16];
-            let Some(mut segment) = segments.pull().map_err(map_deser_err)? else {
-                bail!("expecting 1 segment for child id")
-            };
-            ensure!(segment.left() == 16);
-            segment.pull(&mut id[..]).map_err(map_deser_err)?;
+fn pull<E: std::fmt::Display>(dec: &mut Decoder<impl Read<Error = E>>) -> Result<Header> {
+    let offset = dec.offset();
+    dec.pull().map_err(map_deser_err(offset))
+}
 
-            children.push(RecordId(uuid::Uuid::from_bytes(id)));
-        }
+fn invalid_header<E>(dec: &mut Decoder<impl Read<Error = E>>, header: Header) -> Error {
+    decode_err(dec.offset(), format!("invalid header: {header:?}"))
+}
 
-        Ok(Self {
-            note,
-            children,
-            datetime,
-        })
+fn decode_err(offset: usize, reason: impl Into<String>) -> Error {
+    Error::Decode {
+        offset,
+        reason: reason.into(),
     }
 }
 
-fn map_deser_err<E: std::fmt::Display>(err: ciborium_ll::Error<E>) -> eyre::Error {
-    match err {
-        ciborium_ll::Error::Io(io) => eyre!("io error: {io}"),
-        ciborium_ll::Error::Syntax(offset) => eyre!("syntax error at byte {offset}"),
+fn map_deser_err<E: std::fmt::Display>(
+    offset: usize,
+) -> impl FnOnce(ciborium_ll::Error<E>) -> Error {
+    move |err| match err {
+        ciborium_ll::Error::Io(io) => decode_err(offset, format!("io error: {io}")),
+        ciborium_ll::Error::Syntax(offset) => decode_err(offset, "syntax error"),
     }
 }
 
@@ -323,55 +1255,65 @@ fn zoned_cbor_9581_ser<E>(zdt: &Zoned, enc: &mut Encoder<impl Write<Error = E>>)
 /// <https://www.rfc-editor.org/rfc/rfc9581.html>
 fn zoned_cbor_9581_deser<E: std::fmt::Display>(
     dec: &mut Decoder<impl Read<Error = E>>,
-) -> eyre::Result<Zoned> {
+) -> Result<Zoned> {
     // TODO: this is not very robust
-    match dec.pull().map_err(map_deser_err)? {
+    match pull(dec)? {
         ciborium_ll::Header::Tag(1001) => {}
-        header => bail!("invalid header: {header:?}"),
+        header => return Err(invalid_header(dec, header)),
     };
-    match dec.pull().map_err(map_deser_err)? {
+    match pull(dec)? {
         ciborium_ll::Header::Map(Some(3)) => {}
-        header => bail!("invalid header: {header:?}"),
+        header => return Err(invalid_header(dec, header)),
     };
 
-    match dec.pull().map_err(map_deser_err)? {
+    match pull(dec)? {
         BASE_TIME => {}
-        header => bail!("invalid header: {header:?}"),
+        header => return Err(invalid_header(dec, header)),
     };
-    let secs = match dec.pull().map_err(map_deser_err)? {
+    let secs = match pull(dec)? {
         ciborium_ll::Header::Positive(secs) => secs as i64,
         ciborium_ll::Header::Negative(secs) => !(secs as i64),
-        header => bail!("invalid header: {header:?}"),
+        header => return Err(invalid_header(dec, header)),
     };
 
-    match dec.pull().map_err(map_deser_err)? {
+    match pull(dec)? {
         NANOSECONDS => {}
-        header => bail!("invalid header: {header:?}"),
+        header => return Err(invalid_header(dec, header)),
     };
-    let nsecs = match dec.pull().map_err(map_deser_err)? {
+    let nsecs = match pull(dec)? {
         ciborium_ll::Header::Positive(secs) => secs as i32,
         ciborium_ll::Header::Negative(secs) => 1_000_000_000 + !(secs as i32),
-        header => bail!("invalid header: {header:?}"),
+        header => return Err(invalid_header(dec, header)),
     };
 
-    let timestamp = Timestamp::new(secs, nsecs)?;
+    let timestamp = Timestamp::new(secs, nsecs)
+        .map_err(|err| decode_err(dec.offset(), format!("invalid timestamp: {err}")))?;
 
-    match dec.pull().map_err(map_deser_err)? {
+    match pull(dec)? {
         TIME_ZONE => {}
-        header => bail!("invalid header: {header:?}"),
+        header => return Err(invalid_header(dec, header)),
     };
-    let timezone = match dec.pull().map_err(map_deser_err)? {
+    let timezone = match pull(dec)? {
         ciborium_ll::Header::Text(len) => {
             // we expect only a single segment here
             // and we expect timezone iana names to not be too long
             let mut scratch = [0; 256];
 
+            let offset = dec.offset();
             let mut segments = dec.text(len);
-            let Some(mut segment) = segments.pull().map_err(map_deser_err)? else {
-                bail!("expecting 1 segment for child id")
+            let Some(mut segment) = segments.pull().map_err(map_deser_err(offset))? else {
+                return Err(decode_err(offset, "expecting 1 segment for time zone"));
+            };
+            let Some(tz) = segment
+                .pull(&mut scratch[..])
+                .map_err(map_deser_err(offset))?
+            else {
+                return Err(decode_err(offset, "expecting 1 segment for time zone"));
             };
-            let Some(tz) = segment.pull(&mut scratch[..]).map_err(map_deser_err)? else {
-                bail!("expecting 1 segment for child id")
+
+            let map_tz_err = |source| Error::TimeZone {
+                name: tz.to_owned(),
+                source,
             };
 
             // check if this is an offset
@@ -385,12 +1327,14 @@ fn zoned_cbor_9581_deser<E: std::fmt::Display>(
                 if tz.as_bytes()[0] == b'-' {
                     seconds = -seconds;
                 }
-                Offset::from_seconds(seconds)?.to_time_zone()
+                Offset::from_seconds(seconds)
+                    .map_err(map_tz_err)?
+                    .to_time_zone()
             } else {
-                TimeZone::get(tz)?
+                TimeZone::get(tz).map_err(map_tz_err)?
             }
         }
-        header => bail!("invalid header: {header:?}"),
+        header => return Err(invalid_header(dec, header)),
     };
 
     Ok(Zoned::new(timestamp, timezone))
@@ -399,15 +1343,22 @@ fn zoned_cbor_9581_deser<E: std::fmt::Display>(
 #[cfg(test)]
 mod tests {
     use atuin_client::record::sqlite_store::SqliteStore;
-    use atuin_common::record::{HostId, RecordId};
+    use atuin_client::record::{encryption::PASETO_V4, store::Store};
+    use atuin_common::record::{DecryptedData, Host, HostId, Record, RecordId};
     use hex_literal::hex;
-    use jiff::{civil::datetime, tz::TimeZone};
+    use jiff::{civil::datetime, tz::TimeZone, Span};
     use uuid::uuid;
 
-    use crate::{Client, Note};
+    use crate::{
+        compact,
+        test_util::{paris, paris_note, tagged},
+        Client, CompactOptions, CompactSummary, Error, Note, NoteTree, SkippedRecord,
+        DEFAULT_NOTEBOOK,
+    };
 
     const HOST1: HostId = HostId(uuid!("a64b4e78-435d-45e1-a7f2-8a9d34f6074a"));
     const HOST2: HostId = HostId(uuid!("f1ddfd0e-e3fd-47a7-9e6a-4998279546c9"));
+    const HOST3: HostId = HostId(uuid!("0b1e9d2c-5a3f-4c7e-8d6b-2f4a1c9e7b3d"));
 
     const FOO: RecordId = RecordId(uuid!("b7d8ac79-4e91-4af8-b164-6e14212531a8"));
     const BAR: RecordId = RecordId(uuid!("46924371-80e2-41ab-85ac-c44d9cb90d81"));
@@ -427,18 +1378,39 @@ mod tests {
             .to_zoned(TimeZone::get("Europe/London").unwrap())
             .unwrap();
 
-        let mut client1 = Client::test(store.clone(), HOST1);
-        let mut client2 = Client::test(store.clone(), HOST2);
+        let mut client1 = Client::test(store.clone(), HOST1).await;
+        let mut client2 = Client::test(store.clone(), HOST2).await;
 
         let id1 = client1
-            .add_record("Hello world".to_string(), vec![], dt1.clone())
-            .await;
+            .add_record(Note {
+                note: "Hello world".to_string(),
+                datetime: dt1.clone(),
+                children: vec![],
+                tags: vec![],
+                retracts: vec![],
+            })
+            .await
+            .unwrap();
         let id2 = client1
-            .add_record("Goodbye world".to_string(), vec![id1], dt2.clone())
-            .await;
+            .add_record(Note {
+                note: "Goodbye world".to_string(),
+                datetime: dt2.clone(),
+                children: vec![id1],
+                tags: vec![],
+                retracts: vec![],
+            })
+            .await
+            .unwrap();
         let id3 = client2
-            .add_record("Hello world again".to_string(), vec![], dt3.clone())
-            .await;
+            .add_record(Note {
+                note: "Hello world again".to_string(),
+                datetime: dt3.clone(),
+                children: vec![],
+                tags: vec![],
+                retracts: vec![],
+            })
+            .await
+            .unwrap();
 
         let mut loaded1 = vec![];
         client1
@@ -460,7 +1432,9 @@ mod tests {
                 Note {
                     note: "Hello world again".to_string(),
                     children: vec![],
+                    tags: vec![],
                     datetime: dt3,
+                    retracts: vec![],
                 }
             )]
         );
@@ -474,7 +1448,9 @@ mod tests {
                     Note {
                         note: "Hello world".to_string(),
                         children: vec![],
+                        tags: vec![],
                         datetime: dt1,
+                        retracts: vec![],
                     }
                 ),
                 (
@@ -483,13 +1459,617 @@ mod tests {
                     Note {
                         note: "Goodbye world".to_string(),
                         children: vec![id1],
+                        tags: vec![],
                         datetime: dt2,
+                        retracts: vec![],
                     }
                 )
             ]
         );
     }
 
+    #[tokio::test]
+    async fn tags() {
+        let store = SqliteStore::new(":memory:", 1.0).await.unwrap();
+
+        let dt = paris();
+
+        let mut client1 = Client::test(store.clone(), HOST1).await;
+        let mut client2 = Client::test(store.clone(), HOST2).await;
+
+        let note1 = Note {
+            note: "Hello world".to_string(),
+            datetime: dt.clone(),
+            children: vec![],
+            tags: vec!["work".to_string()],
+            retracts: vec![],
+        };
+        let id1 = client1.add_record(note1.clone()).await.unwrap();
+        client2
+            .add_record(Note {
+                note: "Hello world again".to_string(),
+                datetime: dt.clone(),
+                children: vec![],
+                tags: vec!["home".to_string(), "work".to_string()],
+                retracts: vec![],
+            })
+            .await
+            .unwrap();
+
+        let mut loaded = vec![];
+        client2
+            .load_notes(|host, id, note| loaded.push((host, id, note)))
+            .await
+            .unwrap();
+
+        assert_eq!(loaded, [(HOST1, id1, note1)]);
+        assert_eq!(
+            client2.tags().iter().collect::<Vec<_>>(),
+            [(&"home".to_string(), &1), (&"work".to_string(), &2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn search() {
+        let store = SqliteStore::new(":memory:", 1.0).await.unwrap();
+
+        let mut client1 = Client::test(store.clone(), HOST1).await;
+        let mut client2 = Client::test(store.clone(), HOST2).await;
+
+        let id1 = client1
+            .add_record(paris_note("Refactor the sync loop"))
+            .await
+            .unwrap();
+        let id2 = client1
+            .add_record(tagged(paris_note("Lunch with the team"), &["work"]))
+            .await
+            .unwrap();
+        let id3 = client2
+            .add_record(paris_note("Buy \"milk\" (and eggs)"))
+            .await
+            .unwrap();
+
+        // client2 only knows about its own note until it loads the others
+        let hits = client2.search("sync", 10).await.unwrap();
+        assert!(hits.is_empty());
+
+        client2.load_notes(|_, _, _| {}).await.unwrap();
+        // loading again does not duplicate the index
+        client2.hosts.clear();
+        client2.load_notes(|_, _, _| {}).await.unwrap();
+
+        let hits = client2.search("sync", 10).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, id1);
+        assert_eq!(hits[0].snippet, "Refactor the [sync] loop");
+
+        // prefix match on the last word, and tags are searched
+        let ids: Vec<_> = client2
+            .search("wor", 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.id)
+            .collect();
+        assert_eq!(ids, [id2]);
+
+        // fts syntax is matched literally
+        let ids: Vec<_> = client2
+            .search("\"milk\" (and", 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.id)
+            .collect();
+        assert_eq!(ids, [id3]);
+    }
+
+    #[tokio::test]
+    async fn retract() {
+        let store = SqliteStore::new(":memory:", 1.0).await.unwrap();
+
+        let secret = tagged(paris_note("my password is hunter2"), &["secret"]);
+        let lunch = tagged(paris_note("Lunch with the team"), &["work"]);
+
+        let mut client1 = Client::test(store.clone(), HOST1).await;
+        let mut client2 = Client::test(store.clone(), HOST2).await;
+
+        let id1 = client1.add_record(secret.clone()).await.unwrap();
+        let id2 = client1.add_record(lunch.clone()).await.unwrap();
+        assert_eq!(client1.search("hunter2", 10).await.unwrap().len(), 1);
+
+        let tombstone = client2.retract(&[id1]).await.unwrap();
+        // older clients do not know the tombstone version, so they skip it
+        assert_eq!(store.get(tombstone).await.unwrap().version, "v2");
+
+        // the tombstone is passed on so that the note can be removed
+        let mut tree = NoteTree::new();
+        tree.insert(HOST1, id1, secret);
+        tree.insert(HOST1, id2, lunch);
+        client1
+            .load_notes(|host, id, note| tree.insert(host, id, note))
+            .await
+            .unwrap();
+        assert_eq!(tree.unprocessed().collect::<Vec<_>>(), [id2]);
+        assert!(client1.search("hunter2", 10).await.unwrap().is_empty());
+        assert_eq!(client1.tags().get("secret"), None);
+        assert_eq!(client1.tags().get("work"), Some(&1));
+
+        // a new client gets the same result, whichever host it loads first
+        let mut client3 = Client::test(store.clone(), HOST3).await;
+        let mut tree = NoteTree::new();
+        client3
+            .load_notes(|host, id, note| tree.insert(host, id, note))
+            .await
+            .unwrap();
+        assert_eq!(tree.unprocessed().collect::<Vec<_>>(), [id2]);
+        assert!(tree.is_retracted(id1));
+        assert_eq!(tree.get(tombstone), None);
+        assert!(client3.search("hunter2", 10).await.unwrap().is_empty());
+        assert_eq!(client3.tags().get("secret"), None);
+
+        let records: Vec<_> = client1
+            .records()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|record| record.id)
+            .collect();
+        assert_eq!(records, [id2]);
+        // queries read the loaded notes from the cache, without retracted notes
+        let notes = client3.query().host(HOST1).fetch().await.unwrap();
+        let ids: Vec<_> = notes.map(|(_, id, _)| id).collect();
+        assert_eq!(ids, [id2]);
+        let mut notes = client3
+            .query()
+            .text_contains("HUNTER2")
+            .fetch()
+            .await
+            .unwrap();
+        assert!(notes.next().is_none());
+    }
+
+    #[tokio::test]
+    async fn query() {
+        let store = SqliteStore::new(":memory:", 1.0).await.unwrap();
+        let mut client = Client::test(store.clone(), HOST1).await;
+
+        // notes added by this client can be queried straight away
+        let id1 = client.add_record(paris_note("Buy milk")).await.unwrap();
+        let merged = Note {
+            children: vec![id1],
+            ..paris_note("Shopping")
+        };
+        let id2 = client.add_record(merged.clone()).await.unwrap();
+        let notes: Vec<_> = client
+            .query()
+            .unprocessed()
+            .fetch()
+            .await
+            .unwrap()
+            .collect();
+        assert_eq!(notes, [(HOST1, id2, merged)]);
+
+        // as are notes loaded from other hosts
+        let mut client2 = Client::test(store.clone(), HOST2).await;
+        let id3 = client2.add_record(paris_note("Buy eggs")).await.unwrap();
+        client.load_notes(|_, _, _| {}).await.unwrap();
+        let notes = client.query().text_contains("buy").fetch().await.unwrap();
+        let mut ids: Vec<_> = notes.map(|(_, id, _)| id).collect();
+        ids.sort();
+        let mut expected = [id1, id3];
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn compact() {
+        let store1 = SqliteStore::new(":memory:", 1.0).await.unwrap();
+        let store2 = SqliteStore::new(":memory:", 1.0).await.unwrap();
+
+        let mut client1 = Client::test(store1.clone(), HOST1).await;
+        let id1 = client1.add_record(paris_note("draft one")).await.unwrap();
+        let id2 = client1.add_record(paris_note("draft two")).await.unwrap();
+        let id3 = client1
+            .add_record(Note {
+                children: vec![id1, id2],
+                ..paris_note("final")
+            })
+            .await
+            .unwrap();
+
+        // the second host has a copy of the same records
+        let records = store1.next(HOST1, Note::TAG, 0, 100).await.unwrap();
+        store2.push_batch(records.iter()).await.unwrap();
+        let mut client2 = Client::test(store2.clone(), HOST2).await;
+        client2.load_notes(|_, _, _| {}).await.unwrap();
+
+        let options = CompactOptions {
+            min_age: Span::new().days(1),
+            propagate: true,
+            dry_run: false,
+        };
+        let dry_run = CompactOptions {
+            dry_run: true,
+            ..options
+        };
+        let summary = client1.compact(&dry_run).await.unwrap();
+        assert_eq!(summary.deleted, [id1, id2]);
+        assert_eq!(client1.records().await.unwrap().len(), 3);
+
+        // a negative age is rejected rather than compacting everything
+        let negative = CompactOptions {
+            min_age: Span::new().days(-1),
+            ..options
+        };
+        assert!(matches!(
+            client1.compact(&negative).await,
+            Err(Error::NegativeAge)
+        ));
+        assert_eq!(client1.records().await.unwrap().len(), 3);
+
+        let summary = client1.compact(&options).await.unwrap();
+        assert_eq!(summary.merges, [id3]);
+        // deleted in the order they were written
+        assert_eq!(summary.deleted, [id1, id2]);
+
+        let ids: Vec<_> = client1
+            .records()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|record| record.id)
+            .collect();
+        assert_eq!(ids, [id3]);
+        assert!(client1.search("draft", 10).await.unwrap().is_empty());
+
+        // nothing left to compact
+        let summary = client1.compact(&options).await.unwrap();
+        assert_eq!(summary, CompactSummary::default());
+
+        // the compaction is applied by the second host after syncing
+        let compactions = store1.all_tagged(compact::COMPACTION_TAG).await.unwrap();
+        store2.push_batch(compactions.iter()).await.unwrap();
+        assert_eq!(client2.search("draft", 10).await.unwrap().len(), 2);
+        client2.apply_compactions().await.unwrap();
+        assert!(client2.search("draft", 10).await.unwrap().is_empty());
+        assert_eq!(client2.records().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn skipped_records() {
+        let store = SqliteStore::new(":memory:", 1.0).await.unwrap();
+
+        let dt = paris();
+
+        let mut client1 = Client::test(store.clone(), HOST1).await;
+        let mut client2 = Client::test(store.clone(), HOST2).await;
+
+        let id1 = client1
+            .add_record(Note {
+                note: "Hello world".to_string(),
+                datetime: dt.clone(),
+                children: vec![],
+                tags: vec![],
+                retracts: vec![],
+            })
+            .await
+            .unwrap();
+
+        let note = Note {
+            note: "From the future".to_string(),
+            children: vec![],
+            tags: vec![],
+            datetime: dt.clone(),
+            retracts: vec![],
+        };
+        let future = Record::builder()
+            .data(DecryptedData(note.ser_v0_to_vec()))
+            .tag(Note::TAG.to_string())
+            .idx(1)
+            .host(Host::new(HOST1))
+            .version("v999".to_string())
+            .build()
+            .encrypt::<PASETO_V4>(&client1.key);
+        store.push(&future).await.unwrap();
+
+        let other_key = Record::builder()
+            .data(DecryptedData(note.ser_v1_to_vec()))
+            .tag(Note::TAG.to_string())
+            .idx(2)
+            .host(Host::new(HOST1))
+            .version(Note::VERSION.to_string())
+            .build()
+            .encrypt::<PASETO_V4>(&[0x11; 32]);
+        store.push(&other_key).await.unwrap();
+
+        let mut loaded = vec![];
+        let skipped = client2
+            .load_notes(|host, id, note| loaded.push((host, id, note)))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            loaded,
+            [(
+                HOST1,
+                id1,
+                Note {
+                    note: "Hello world".to_string(),
+                    children: vec![],
+                    tags: vec![],
+                    datetime: dt,
+                    retracts: vec![],
+                }
+            )]
+        );
+
+        let [skipped1, skipped2] = &skipped[..] else {
+            panic!("expected 2 skipped records: {skipped:?}");
+        };
+        assert!(
+            matches!(
+                skipped1,
+                SkippedRecord { host: HOST1, idx: 1, id, reason: Error::UnknownVersion { version, .. } }
+                    if *id == future.id && version == "v999"
+            ),
+            "{skipped1:?}"
+        );
+        assert!(
+            matches!(
+                skipped2,
+                SkippedRecord { host: HOST1, idx: 2, id, reason: Error::Decrypt { .. } }
+                    if *id == other_key.id
+            ),
+            "{skipped2:?}"
+        );
+
+        // skipped records are only reported once
+        let skipped = client2.load_notes(|_, _, _| {}).await.unwrap();
+        assert!(skipped.is_empty());
+    }
+
+    #[tokio::test]
+    async fn note_cache() {
+        let store = SqliteStore::new(":memory:", 1.0).await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let search = dir.path().join("search.db");
+        let cache = dir.path().join("cache.db");
+        let open = |key: [u8; 32]| {
+            Client::open(
+                store.clone(),
+                HOST2,
+                key,
+                DEFAULT_NOTEBOOK,
+                search.to_str().unwrap(),
+                cache.to_str().unwrap(),
+                1.0,
+            )
+        };
+
+        let mut client1 = Client::test(store.clone(), HOST1).await;
+        let hello = tagged(paris_note("Hello world"), &["work"]);
+        let id1 = client1.add_record(hello.clone()).await.unwrap();
+
+        // with the wrong key, the record is skipped
+        let mut client2 = open([0x11; 32]).await.unwrap();
+        let mut loaded = vec![];
+        let skipped = client2
+            .load_notes(|host, id, note| loaded.push((host, id, note)))
+            .await
+            .unwrap();
+        assert!(loaded.is_empty());
+        assert_eq!(skipped.len(), 1);
+
+        // with the right key, skipped records are retried
+        let mut client2 = open(client1.key).await.unwrap();
+        let mut loaded = vec![];
+        let skipped = client2
+            .load_notes(|host, id, note| loaded.push((host, id, note)))
+            .await
+            .unwrap();
+        assert_eq!(loaded, [(HOST1, id1, hello.clone())]);
+        assert!(skipped.is_empty());
+
+        let id2 = client1
+            .add_record(tagged(paris_note("Goodbye world"), &["work"]))
+            .await
+            .unwrap();
+
+        // cached notes are replayed without decrypting them again,
+        // and only new records are loaded from the store
+        let mut client2 = open([0x11; 32]).await.unwrap();
+        let mut loaded = vec![];
+        let skipped = client2
+            .load_notes(|host, id, note| loaded.push((host, id, note)))
+            .await
+            .unwrap();
+        assert_eq!(loaded, [(HOST1, id1, hello.clone())]);
+        assert!(matches!(
+            &skipped[..],
+            [SkippedRecord { host: HOST1, idx: 1, id, .. }] if *id == id2
+        ));
+        assert_eq!(client2.tags().get("work"), Some(&1));
+        assert_eq!(client2.search("hello", 10).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn note_cache_same_host() {
+        let store = SqliteStore::new(":memory:", 1.0).await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let search = dir.path().join("search.db");
+        let cache = dir.path().join("cache.db");
+        let open = || {
+            Client::open(
+                store.clone(),
+                HOST1,
+                [0x55; 32],
+                DEFAULT_NOTEBOOK,
+                search.to_str().unwrap(),
+                cache.to_str().unwrap(),
+                1.0,
+            )
+        };
+
+        // two clients for the same host share the note cache, such as the helper and the app
+        let mut client1 = open().await.unwrap();
+        let mut client2 = open().await.unwrap();
+        client1.load_notes(|_, _, _| {}).await.unwrap();
+        client2.load_notes(|_, _, _| {}).await.unwrap();
+
+        let id1 = client1.add_record(paris_note("Hello world")).await.unwrap();
+        let id2 = client2
+            .add_record(paris_note("Goodbye world"))
+            .await
+            .unwrap();
+        client1.load_notes(|_, _, _| {}).await.unwrap();
+
+        let mut loaded = vec![];
+        let mut client3 = open().await.unwrap();
+        client3
+            .load_notes(|_, id, _| loaded.push(id))
+            .await
+            .unwrap();
+        loaded.sort();
+        let mut expected = [id1, id2];
+        expected.sort();
+        assert_eq!(loaded, expected);
+    }
+
+    #[tokio::test]
+    async fn notebooks() {
+        let store = SqliteStore::new(":memory:", 1.0).await.unwrap();
+        let open = |host, notebook| {
+            Client::open(
+                store.clone(),
+                host,
+                [0x55; 32],
+                notebook,
+                ":memory:",
+                ":memory:",
+                1.0,
+            )
+        };
+
+        let mut personal = open(HOST1, DEFAULT_NOTEBOOK).await.unwrap();
+        let mut work = open(HOST1, "work").await.unwrap();
+        let id1 = personal
+            .add_record(paris_note("Hello world"))
+            .await
+            .unwrap();
+        let id2 = work.add_record(paris_note("Hello work")).await.unwrap();
+        personal.create_notebook("empty").await.unwrap();
+        personal.create_notebook("empty").await.unwrap();
+        assert!(matches!(
+            personal.create_notebook("no spaces").await,
+            Err(Error::InvalidNotebook(_))
+        ));
+
+        // notes from other notebooks are not loaded
+        let mut loaded = vec![];
+        let mut client = open(HOST2, DEFAULT_NOTEBOOK).await.unwrap();
+        client.load_notes(|_, id, _| loaded.push(id)).await.unwrap();
+        assert_eq!(loaded, [id1]);
+
+        let mut loaded = vec![];
+        let mut client = open(HOST2, "work").await.unwrap();
+        client.load_notes(|_, id, _| loaded.push(id)).await.unwrap();
+        assert_eq!(loaded, [id2]);
+
+        assert_eq!(
+            client.notebooks().await.unwrap(),
+            [DEFAULT_NOTEBOOK, "empty", "work"]
+        );
+    }
+
+    #[tokio::test]
+    async fn import() {
+        let store = SqliteStore::new(":memory:", 1.0).await.unwrap();
+
+        let mut client1 = Client::test(store.clone(), HOST1).await;
+        let id1 = client1.add_record(paris_note("Hello world")).await.unwrap();
+
+        let backup = SqliteStore::new(":memory:", 1.0).await.unwrap();
+        let mut client2 = Client::test(backup, HOST2).await;
+        let imported = client2
+            .import(vec![
+                (id1, paris_note("Hello world")),
+                (FOO, paris_note("Foo")),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(imported, [id1, FOO]);
+
+        // restoring the backup only adds the notes that are missing
+        let records = client2.records().await.unwrap();
+        let notes = records.into_iter().map(|r| (r.id, r.note)).collect();
+        let imported = client1.import(notes).await.unwrap();
+        assert_eq!(imported, [FOO]);
+
+        let records = client1.records().await.unwrap();
+        assert_eq!(
+            records.iter().map(|r| (r.idx, r.id)).collect::<Vec<_>>(),
+            [(0, id1), (1, FOO)]
+        );
+        assert_eq!(records[1].note, paris_note("Foo"));
+
+        // ids are unique across notebooks too
+        let mut work = Client::open(
+            store.clone(),
+            HOST1,
+            [0x55; 32],
+            "work",
+            ":memory:",
+            ":memory:",
+            1.0,
+        )
+        .await
+        .unwrap();
+        let imported = work
+            .import(vec![(id1, paris_note("Hello world"))])
+            .await
+            .unwrap();
+        assert!(imported.is_empty());
+    }
+
+    #[tokio::test]
+    async fn import_deleted() {
+        let store = SqliteStore::new(":memory:", 1.0).await.unwrap();
+        let mut client = Client::test(store.clone(), HOST1).await;
+
+        let draft1 = paris_note("draft one");
+        let draft2 = paris_note("draft two");
+        let id1 = client.add_record(draft1.clone()).await.unwrap();
+        let id2 = client.add_record(draft2.clone()).await.unwrap();
+        let merge = Note {
+            children: vec![id1],
+            ..paris_note("merge")
+        };
+        let id3 = client.add_record(merge.clone()).await.unwrap();
+        client
+            .add_record(Note {
+                children: vec![id3, id2],
+                ..paris_note("final")
+            })
+            .await
+            .unwrap();
+        let backup = vec![
+            (id1, draft1),
+            (id2, draft2),
+            (id3, merge),
+            (FOO, paris_note("Foo")),
+        ];
+
+        let summary = client.compact(&CompactOptions::default()).await.unwrap();
+        assert_eq!(summary.deleted.len(), 3);
+        // a note that was retracted before it was synced to this host
+        client.retract(&[FOO]).await.unwrap();
+
+        let imported = client.import(backup).await.unwrap();
+        assert!(imported.is_empty());
+        assert_eq!(client.records().await.unwrap().len(), 1);
+    }
+
     #[test]
     fn ser_deser1() {
         let note = Note {
@@ -498,6 +2078,8 @@ mod tests {
                 .to_zoned(TimeZone::get("Europe/Paris").unwrap())
                 .unwrap(),
             children: vec![FOO, BAR, BAZ],
+            tags: vec![],
+            retracts: vec![],
         };
 
         let buf = note.ser_v0_to_vec();
@@ -547,6 +2129,8 @@ mod tests {
                 .to_zoned(TimeZone::get("America/New_York").unwrap())
                 .unwrap(),
             children: vec![FOO, BAR, BAZ],
+            tags: vec![],
+            retracts: vec![],
         };
 
         let buf = note.ser_v0_to_vec();
@@ -583,4 +2167,117 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn deser_errors() {
+        // array(2) rather than array(3)
+        let err = Note::deser_v0(&hex!("82")).unwrap_err();
+        assert!(matches!(err, Error::Decode { offset: 1, .. }), "{err:?}");
+
+        // truncated note text
+        let err = Note::deser_v0(&hex!("83 6f 5468")).unwrap_err();
+        assert!(matches!(err, Error::Decode { .. }), "{err:?}");
+
+        let err = Note::deser_v0(&hex!(
+            "83" // array(3)
+                "60" // text(0)
+                "d9 03e9" // tag(extended_time)
+                    "a3" // map(3)
+                        "01 00" // basetime: 0
+                        "28 00" // nanoseconds: 0
+                        "0a 69" // timezone: text(9)
+                            "4e6f742f415a6f6e65" // "Not/AZone"
+                "80" // array(0)
+        ))
+        .unwrap_err();
+        assert!(
+            matches!(&err, Error::TimeZone { name, .. } if name == "Not/AZone"),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn ser_deser_v1() {
+        let note = Note {
+            note: "This is my note".to_owned(),
+            datetime: datetime(2024, 11, 9, 12, 19, 22, 0)
+                .to_zoned(TimeZone::get("Europe/Paris").unwrap())
+                .unwrap(),
+            children: vec![FOO, BAR, BAZ],
+            tags: vec![],
+            retracts: vec![],
+        };
+
+        let buf = note.ser_v1_to_vec();
+        let note2 = Note::deser_v1(&buf).unwrap();
+        assert_eq!(note, note2);
+
+        assert_eq!(
+            buf,
+            hex!(
+                "a3" // map(3)
+                    "00" // note
+                    "6f" // text(15)
+                        "54686973206973206d79206e6f7465"
+
+                    "01" // datetime
+                    "d9 03e9" // tag(extended_time)
+                        "a3" // map(3)
+                           "01 1a 672f453a" // basetime: unsigned(1,731,151,162)
+                           "28 00" // nanoseconds: 0
+                           "0a 6c" // timezone: text(12)
+                              "4575726f70652f5061726973" // "Europe/Paris"
+
+                    "02" // children
+                    "83" // array(3)
+                        "d8 25" // tag(uuid_bytes)
+                            "50" // bytes(16)
+                                "b7d8ac794e914af8b1646e14212531a8"
+                        "d8 25" // tag(uuid_bytes)
+                            "50" // bytes(16)
+                                "4692437180e241ab85acc44d9cb90d81"
+                        "d8 25" // tag(uuid_bytes)
+                            "50" // bytes(16)
+                                "3965e843d386424d9c32f5d0d4234641"
+            )
+        );
+    }
+
+    #[test]
+    fn deser_v1_unknown_keys() {
+        let note = Note::deser_v1(&hex!(
+            "a3" // map(3)
+                "01" // datetime
+                "d9 03e9" // tag(extended_time)
+                    "a3" // map(3)
+                       "01 1a 672f453a" // basetime: unsigned(1,731,151,162)
+                       "28 00" // nanoseconds: 0
+                       "0a 6c" // timezone: text(12)
+                          "4575726f70652f5061726973" // "Europe/Paris"
+
+                "18 2a" // unknown key 42
+                "83" // array(3)
+                    "01" // 1
+                    "61 78" // "x"
+                    "a1 01 c1 41 00" // {1: tag(1) h'00'}
+
+                "00" // note
+                "6f" // text(15)
+                    "54686973206973206d79206e6f7465"
+        ))
+        .unwrap();
+
+        assert_eq!(
+            note,
+            Note {
+                note: "This is my note".to_owned(),
+                datetime: datetime(2024, 11, 9, 12, 19, 22, 0)
+                    .to_zoned(TimeZone::get("Europe/Paris").unwrap())
+                    .unwrap(),
+                children: vec![],
+                tags: vec![],
+                retracts: vec![],
+            }
+        );
+    }
 }
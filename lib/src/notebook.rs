@@ -0,0 +1,84 @@
+use atuin_client::record::encryption::PASETO_V4;
+use atuin_common::record::{EncryptedData, Record};
+
+use crate::{decode_err, Error, Note, Result};
+
+/// The notebook that notes were written to before notebooks existed.
+pub const DEFAULT_NOTEBOOK: &str = "default";
+
+/// Records announcing a new notebook, so that empty notebooks are synced to other hosts too.
+pub(crate) const NOTEBOOK_TAG: &str = "note_lsm::notebook";
+pub(crate) const NOTEBOOK_VERSION: &str = "v0";
+
+/// The atuin record tag used for the notes in `notebook`.
+///
+/// Each notebook uses a separate tag so that its records can be loaded independently.
+/// The default notebook keeps the original tag.
+pub(crate) fn record_tag(notebook: &str) -> String {
+    if notebook == DEFAULT_NOTEBOOK {
+        Note::TAG.to_owned()
+    } else {
+        format!("{}::{notebook}", Note::TAG)
+    }
+}
+
+/// The notebook whose notes use the atuin record `tag`, if any.
+pub(crate) fn from_record_tag(tag: &str) -> Option<&str> {
+    if tag == Note::TAG {
+        return Some(DEFAULT_NOTEBOOK);
+    }
+    tag.strip_prefix(Note::TAG)?.strip_prefix("::")
+}
+
+/// Notebook names are used in record tags and file names, so they are kept simple.
+pub(crate) fn validate(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidNotebook(name.to_owned()))
+    }
+}
+
+/// Decrypt a notebook announcement, returning the notebook name.
+pub(crate) fn decrypt(record: Record<EncryptedData>, key: &[u8; 32]) -> Result<String> {
+    let id = record.id;
+    if record.version != NOTEBOOK_VERSION {
+        return Err(Error::UnknownVersion {
+            id,
+            version: record.version,
+        });
+    }
+
+    let record = record
+        .decrypt::<PASETO_V4>(key)
+        .map_err(|err| Error::Decrypt {
+            id,
+            source: err.into(),
+        })?;
+
+    String::from_utf8(record.data.0)
+        .map_err(|err| decode_err(err.utf8_error().valid_up_to(), "invalid utf8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_record_tag, record_tag, validate, DEFAULT_NOTEBOOK};
+
+    #[test]
+    fn tags() {
+        assert_eq!(record_tag(DEFAULT_NOTEBOOK), "note_lsm::note");
+        assert_eq!(record_tag("work"), "note_lsm::note::work");
+        assert_eq!(from_record_tag("note_lsm::note"), Some(DEFAULT_NOTEBOOK));
+        assert_eq!(from_record_tag("note_lsm::note::work"), Some("work"));
+        assert_eq!(from_record_tag("note_lsm::notebook"), None);
+
+        assert!(validate("work-2024_q4").is_ok());
+        assert!(validate("").is_err());
+        assert!(validate("../work").is_err());
+    }
+}
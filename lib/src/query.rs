@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+
+use atuin_common::record::{HostId, RecordId};
+use jiff::Timestamp;
+
+use crate::{Client, Note, NoteRecord, NoteTree, Result};
+
+/// Filters over notes, started with [`NoteTree::query`], [`Client::query`] or [`Query::records`].
+///
+/// All filters must match for a note to be returned.
+#[derive(Debug, Clone)]
+pub struct Query<S> {
+    source: S,
+    filter: Filter,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Filter {
+    since: Option<Timestamp>,
+    until: Option<Timestamp>,
+    host: Option<HostId>,
+    unprocessed: bool,
+    /// lowercase, to match case-insensitively
+    text: Option<String>,
+    tag: Option<String>,
+}
+
+impl<S> Query<S> {
+    pub(crate) fn new(source: S) -> Self {
+        Self {
+            source,
+            filter: Filter::default(),
+        }
+    }
+
+    /// Only notes written at or after this time.
+    pub fn since(mut self, since: impl Into<Option<Timestamp>>) -> Self {
+        self.filter.since = since.into();
+        self
+    }
+
+    /// Only notes written before this time.
+    pub fn until(mut self, until: impl Into<Option<Timestamp>>) -> Self {
+        self.filter.until = until.into();
+        self
+    }
+
+    /// Only notes written by this host.
+    pub fn host(mut self, host: impl Into<Option<HostId>>) -> Self {
+        self.filter.host = host.into();
+        self
+    }
+
+    /// Only notes that have not been merged into another note.
+    pub fn unprocessed(mut self) -> Self {
+        self.filter.unprocessed = true;
+        self
+    }
+
+    /// Only notes whose text contains `text`, ignoring case.
+    pub fn text_contains(mut self, text: &str) -> Self {
+        self.filter.text = Some(text.to_lowercase());
+        self
+    }
+
+    /// Only notes with this tag.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.filter.tag = Some(tag.to_owned());
+        self
+    }
+}
+
+impl Filter {
+    fn matches(&self, host: HostId, note: &Note) -> bool {
+        let time = note.datetime.timestamp();
+        self.since.is_none_or(|since| time >= since)
+            && self.until.is_none_or(|until| time < until)
+            && self.host.is_none_or(|h| h == host)
+            && self
+                .text
+                .as_ref()
+                .is_none_or(|text| note.note.to_lowercase().contains(text))
+            && self.tag.as_ref().is_none_or(|tag| note.tags.contains(tag))
+    }
+
+    /// The notes merged into any of `notes`, which do not match if only unprocessed notes should.
+    fn merged<'a>(&self, notes: impl Iterator<Item = &'a Note>) -> HashSet<RecordId> {
+        if !self.unprocessed {
+            return HashSet::new();
+        }
+        notes
+            .flat_map(|note| note.children.iter().copied())
+            .collect()
+    }
+}
+
+impl<'a> Query<&'a NoteTree> {
+    /// The matching notes, ordered by id, which is roughly oldest first.
+    pub fn iter(self) -> impl DoubleEndedIterator<Item = (HostId, RecordId, &'a Note)> + 'a {
+        let tree = self.source;
+        let ids: Box<dyn DoubleEndedIterator<Item = RecordId> + 'a> = if self.filter.unprocessed {
+            Box::new(tree.unprocessed())
+        } else {
+            Box::new(tree.iter().map(|(id, _)| id))
+        };
+
+        ids.filter_map(move |id| {
+            let note = tree.get(id)?;
+            let host = tree.host(id)?;
+            self.filter.matches(host, note).then_some((host, id, note))
+        })
+    }
+}
+
+impl Query<&Client> {
+    /// The matching notes, ordered by id.
+    ///
+    /// Notes are read from the local note cache, so nothing is decrypted,
+    /// but only notes added by this client or loaded with [`Client::load_notes`] are included.
+    pub async fn fetch(self) -> Result<impl DoubleEndedIterator<Item = (HostId, RecordId, Note)>> {
+        // retracted notes are removed from the cache, but the tombstones are kept
+        let mut notes = self.source.cache.notes().await?;
+        notes.retain(|(_, _, note)| !note.is_tombstone());
+        notes.sort_unstable_by_key(|(_, id, _)| *id);
+
+        let merged = self.filter.merged(notes.iter().map(|(_, _, note)| note));
+        Ok(notes.into_iter().filter(move |(host, id, note)| {
+            !merged.contains(id) && self.filter.matches(*host, note)
+        }))
+    }
+}
+
+impl<'a> Query<&'a [NoteRecord]> {
+    /// Filter note records, such as those from [`Client::records`], without building a [`NoteTree`].
+    pub fn records(records: &'a [NoteRecord]) -> Self {
+        Self::new(records)
+    }
+
+    /// The matching records, in the order they were given. Tombstones never match.
+    pub fn iter(self) -> impl DoubleEndedIterator<Item = &'a NoteRecord> + 'a {
+        let merged = self
+            .filter
+            .merged(self.source.iter().map(|record| &record.note));
+        self.source.iter().filter(move |record| {
+            !record.note.is_tombstone()
+                && !merged.contains(&record.id)
+                && self.filter.matches(record.host, &record.note)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_util::{id, note, tagged, HOST, HOST2},
+        NoteRecord, NoteTree, Query,
+    };
+
+    #[test]
+    fn query() {
+        let mut tree = NoteTree::new();
+        tree.insert(HOST, id(1), note("Buy milk", 9, &[]));
+        tree.insert(HOST2, id(2), tagged(note("buy eggs", 10, &[]), &["home"]));
+        tree.insert(HOST, id(3), tagged(note("shopping", 11, &[1]), &["home"]));
+        tree.insert(HOST2, id(4), note("call bob", 12, &[]));
+
+        let ids = |query: crate::Query<&NoteTree>| -> Vec<_> {
+            query.iter().map(|(_, id, _)| id).collect()
+        };
+
+        assert_eq!(ids(tree.query()), [id(1), id(2), id(3), id(4)]);
+        assert_eq!(ids(tree.query().unprocessed()), [id(2), id(3), id(4)]);
+        assert_eq!(ids(tree.query().text_contains("BUY")), [id(1), id(2)]);
+        assert_eq!(ids(tree.query().tag("home").unprocessed()), [id(2), id(3)]);
+        assert_eq!(ids(tree.query().host(HOST2)), [id(2), id(4)]);
+
+        let since: jiff::Timestamp = "2024-11-09T10:00Z".parse().unwrap();
+        let until: jiff::Timestamp = "2024-11-09T12:00Z".parse().unwrap();
+        assert_eq!(ids(tree.query().since(since).until(until)), [id(2), id(3)]);
+        // unset filters match everything
+        assert_eq!(ids(tree.query().since(None).host(None)).len(), 4);
+
+        let newest: Vec<_> = tree
+            .query()
+            .unprocessed()
+            .iter()
+            .rev()
+            .take(1)
+            .map(|(host, id, _)| (host, id))
+            .collect();
+        assert_eq!(newest, [(HOST2, id(4))]);
+    }
+
+    #[test]
+    fn records() {
+        let records: Vec<_> = [
+            (HOST, 1, note("Buy milk", 9, &[])),
+            (HOST2, 2, tagged(note("buy eggs", 10, &[]), &["home"])),
+            (HOST, 3, tagged(note("shopping", 11, &[1]), &["home"])),
+            (HOST2, 4, note("call bob", 12, &[])),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(idx, (host, n, note))| NoteRecord {
+            host,
+            idx: idx as u64,
+            id: id(n),
+            note,
+        })
+        .collect();
+
+        let ids = |query: Query<&[NoteRecord]>| -> Vec<_> {
+            query.iter().map(|record| record.id).collect()
+        };
+
+        assert_eq!(ids(Query::records(&records)).len(), 4);
+        assert_eq!(
+            ids(Query::records(&records).unprocessed()),
+            [id(2), id(3), id(4)]
+        );
+        assert_eq!(
+            ids(Query::records(&records).tag("home").host(HOST)),
+            [id(3)]
+        );
+
+        let since: jiff::Timestamp = "2024-11-09T10:00Z".parse().unwrap();
+        let until: jiff::Timestamp = "2024-11-09T12:00Z".parse().unwrap();
+        assert_eq!(
+            ids(Query::records(&records).since(since).until(until)),
+            [id(2), id(3)]
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+
+use atuin_common::record::{HostId, RecordId};
+use jiff::{civil::Date, ToSpan, Zoned};
+use serde::Deserialize;
+
+use crate::{Note, NoteTree};
+
+/// The time buckets that notes are rolled up into, see [`plan_rollups`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RollupPeriod {
+    /// One note per day, titled like `2026-10-18`.
+    Daily,
+    /// One note per ISO week, Monday to Sunday, titled like `2026-W42`.
+    Weekly,
+}
+
+impl RollupPeriod {
+    /// The tag added to rollup notes, so they can be told apart from notes merged by hand.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Self::Daily => "rollup/daily",
+            Self::Weekly => "rollup/weekly",
+        }
+    }
+
+    /// The rollup period of a note created by [`plan_rollups`], if it is one.
+    fn of(note: &Note) -> Option<Self> {
+        [Self::Daily, Self::Weekly]
+            .into_iter()
+            .find(|period| note.tags.iter().any(|tag| tag == period.tag()))
+    }
+
+    /// Whether the note was merged automatically, by [`plan_rollups`] or an import of daily notes.
+    pub(crate) fn is_rollup(note: &Note) -> bool {
+        note.tags.iter().any(|tag| tag.starts_with("rollup/"))
+    }
+
+    /// The first day of the bucket containing `date`.
+    fn start(self, date: Date) -> Date {
+        match self {
+            Self::Daily => date,
+            Self::Weekly => {
+                let offset = date.weekday().to_monday_zero_offset();
+                date.saturating_sub(i64::from(offset).days())
+            }
+        }
+    }
+
+    fn title(self, start: Date) -> String {
+        match self {
+            Self::Daily => start.to_string(),
+            Self::Weekly => {
+                let week = start.to_iso_week_date();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+        }
+    }
+}
+
+/// Merge notes for each completed `period` that still has unprocessed notes, oldest first.
+///
+/// Notes are bucketed by their date in the time zone of `now`, and the bucket containing `now`
+/// is left alone as more notes might still be added to it.
+/// Each rollup merges all unprocessed notes in its bucket, except rollups of longer periods,
+/// so weekly rollups merge the daily rollups of that week.
+/// A rollup of the same period is merged again only if new notes turned up in its bucket.
+///
+/// Each bucket is rolled up by the host that wrote its first rollup, so buckets already rolled up
+/// by a host other than `host` are skipped, and that host merges any new notes once they sync.
+///
+/// The notes still need to be added with [`Client::add_record`](crate::Client::add_record).
+pub fn plan_rollups(tree: &NoteTree, period: RollupPeriod, host: HostId, now: &Zoned) -> Vec<Note> {
+    let tz = now.time_zone();
+    let current = period.start(now.date());
+    let bucket = |note: &Note| period.start(note.datetime.with_time_zone(tz.clone()).date());
+
+    // notes are ordered by id, so the first rollup seen in each bucket is the oldest
+    let mut owners = BTreeMap::new();
+    for (id, note) in tree.iter() {
+        if RollupPeriod::of(note) == Some(period) {
+            if let Some(owner) = tree.host(id) {
+                owners.entry(bucket(note)).or_insert(owner);
+            }
+        }
+    }
+
+    let mut buckets: BTreeMap<Date, Vec<(RecordId, &Note)>> = BTreeMap::new();
+    for id in tree.unprocessed() {
+        let Some(note) = tree.get(id) else {
+            continue;
+        };
+        if RollupPeriod::of(note).is_some_and(|p| p > period) {
+            continue;
+        }
+        let start = bucket(note);
+        if start < current && owners.get(&start).is_none_or(|&owner| owner == host) {
+            buckets.entry(start).or_default().push((id, note));
+        }
+    }
+
+    buckets
+        .into_iter()
+        .filter(|(_, notes)| {
+            notes
+                .iter()
+                .any(|(_, note)| RollupPeriod::of(note) != Some(period))
+        })
+        .map(|(start, mut notes)| {
+            notes.sort_by(|(_, a), (_, b)| a.datetime.cmp(&b.datetime));
+            let (_, last) = notes.last().expect("buckets are not empty");
+            Note {
+                note: period.title(start),
+                // the end of the bucket might not exist in the time zone, so use the last note's time
+                datetime: last.datetime.clone(),
+                children: notes.iter().map(|(id, _)| *id).collect(),
+                tags: vec![period.tag().to_owned()],
+                retracts: vec![],
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::{civil::datetime, tz::TimeZone, Zoned};
+
+    use super::{plan_rollups, RollupPeriod};
+    use crate::{
+        test_util::{id, note_at, tagged, HOST, HOST2},
+        Note, NoteTree,
+    };
+
+    fn at(day: i8, hour: i8) -> Zoned {
+        datetime(2026, 10, day, hour, 0, 0, 0)
+            .to_zoned(TimeZone::get("Europe/London").unwrap())
+            .unwrap()
+    }
+
+    fn note(day: i8, hour: i8) -> Note {
+        note_at("note", at(day, hour), &[])
+    }
+
+    #[test]
+    fn daily() {
+        let mut tree = NoteTree::new();
+        tree.insert(HOST, id(1), note(12, 9));
+        tree.insert(HOST, id(2), note(12, 8));
+        // midnight in London is still the previous day in UTC
+        tree.insert(HOST, id(3), note(13, 0));
+        tree.insert(HOST, id(4), note(18, 9));
+
+        let rollups = plan_rollups(&tree, RollupPeriod::Daily, HOST, &at(18, 12));
+        let rollups: Vec<_> = rollups
+            .iter()
+            .map(|note| (note.note.as_str(), note.children.as_slice()))
+            .collect();
+        assert_eq!(
+            rollups,
+            [
+                ("2026-10-12", &[id(2), id(1)][..]),
+                ("2026-10-13", &[id(3)][..]),
+            ]
+        );
+
+        // a rollup is only merged again if there are new notes for its day
+        for (n, rollup) in (10..).zip(plan_rollups(&tree, RollupPeriod::Daily, HOST, &at(18, 12))) {
+            tree.insert(HOST, id(n), rollup);
+        }
+        assert!(plan_rollups(&tree, RollupPeriod::Daily, HOST, &at(18, 12)).is_empty());
+        tree.insert(HOST, id(5), note(12, 10));
+        let rollups = plan_rollups(&tree, RollupPeriod::Daily, HOST, &at(18, 12));
+        assert_eq!(rollups.len(), 1);
+        assert_eq!(rollups[0].children, [id(10), id(5)]);
+    }
+
+    #[test]
+    fn weekly() {
+        let mut tree = NoteTree::new();
+        tree.insert(HOST, id(1), note(5, 9));
+        tree.insert(HOST, id(2), note(11, 9));
+        tree.insert(HOST, id(3), note(12, 9));
+
+        for (n, rollup) in (10..).zip(plan_rollups(&tree, RollupPeriod::Daily, HOST, &at(18, 12))) {
+            tree.insert(HOST, id(n), rollup);
+        }
+        let weekly = plan_rollups(&tree, RollupPeriod::Weekly, HOST, &at(18, 12));
+        let weekly: Vec<_> = weekly
+            .iter()
+            .map(|note| (note.note.as_str(), note.children.as_slice(), &note.tags))
+            .collect();
+        assert_eq!(
+            weekly,
+            [(
+                "2026-W41",
+                &[id(10), id(11)][..],
+                &vec!["rollup/weekly".to_owned()]
+            )]
+        );
+
+        // daily rollups leave weekly rollups alone
+        for (n, rollup) in (20..).zip(plan_rollups(&tree, RollupPeriod::Weekly, HOST, &at(18, 12)))
+        {
+            tree.insert(HOST, id(n), rollup);
+        }
+        assert!(plan_rollups(&tree, RollupPeriod::Daily, HOST, &at(25, 12)).is_empty());
+    }
+
+    #[test]
+    fn other_host() {
+        let mut tree = NoteTree::new();
+        tree.insert(HOST, id(1), note(12, 9));
+        tree.insert(HOST, id(2), note(13, 9));
+        // HOST2 already rolled up the 12th, before it had seen the straggler
+        let rollup = tagged(note_at("2026-10-12", at(12, 9), &[1]), &["rollup/daily"]);
+        tree.insert(HOST2, id(10), rollup);
+        tree.insert(HOST, id(3), note(12, 10));
+
+        let rollups = plan_rollups(&tree, RollupPeriod::Daily, HOST, &at(18, 12));
+        let rollups: Vec<_> = rollups.iter().map(|note| note.note.as_str()).collect();
+        assert_eq!(rollups, ["2026-10-13"]);
+
+        // HOST2 merges the straggler into its rollup instead
+        let rollups = plan_rollups(&tree, RollupPeriod::Daily, HOST2, &at(18, 12));
+        assert_eq!(rollups[0].children, [id(10), id(3)]);
+    }
+}
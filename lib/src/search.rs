@@ -0,0 +1,219 @@
+use std::str::FromStr;
+
+use atuin_common::record::RecordId;
+use sqlx::{sqlite::SqlitePool, Row};
+
+use crate::{sqlite, Error, Note, Result};
+
+/// A note matching a search query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub id: RecordId,
+    /// An extract of the note text around the match, with matched terms wrapped in `[` `]`.
+    pub snippet: String,
+    /// How well the note matched, lower is better.
+    pub rank: f64,
+}
+
+/// Full-text index over note text and tags, using an sqlite FTS5 table.
+///
+/// The index is only derived from the record store, so it can be deleted and rebuilt at any time.
+#[derive(Debug, Clone)]
+pub(crate) struct SearchIndex {
+    pool: SqlitePool,
+}
+
+impl SearchIndex {
+    pub(crate) async fn new(path: &str, timeout: f64) -> Result<Self> {
+        let pool = sqlite::open(path, timeout)
+            .await
+            .map_err(Error::index("opening search index"))?;
+
+        sqlx::query(
+            "create table if not exists indexed (
+                id text primary key not null
+            )",
+        )
+        .execute(&pool)
+        .await
+        .map_err(Error::index("creating search index"))?;
+
+        sqlx::query(
+            "create virtual table if not exists notes_fts using fts5(
+                id unindexed,
+                note,
+                tags,
+                tokenize = 'porter unicode61'
+            )",
+        )
+        .execute(&pool)
+        .await
+        .map_err(Error::index("creating search index"))?;
+
+        Ok(Self { pool })
+    }
+
+    /// Add notes to the index. Notes that are already indexed are ignored.
+    pub(crate) async fn insert(&self, notes: &[(RecordId, Note)]) -> Result<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(Error::index("starting search index transaction"))?;
+
+        for (id, note) in notes {
+            let id = id.0.as_hyphenated().to_string();
+
+            let inserted = sqlx::query("insert or ignore into indexed (id) values (?1)")
+                .bind(&id)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::index("indexing note"))?;
+            if inserted.rows_affected() == 0 {
+                continue;
+            }
+
+            sqlx::query("insert into notes_fts (id, note, tags) values (?1, ?2, ?3)")
+                .bind(&id)
+                .bind(&note.note)
+                .bind(note.tags.join(" "))
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::index("indexing note"))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(Error::index("committing search index transaction"))
+    }
+
+    /// Remove retracted or deleted notes from the index.
+    ///
+    /// They stay marked as indexed, so that they are not added back if they are loaded again.
+    pub(crate) async fn remove(&self, ids: &[RecordId]) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(Error::index("starting search index transaction"))?;
+
+        for id in ids {
+            let id = id.0.as_hyphenated().to_string();
+
+            sqlx::query("delete from notes_fts where id = ?1")
+                .bind(&id)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::index("removing note"))?;
+
+            sqlx::query("insert or ignore into indexed (id) values (?1)")
+                .bind(&id)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::index("removing note"))?;
+        }
+
+        // deleted rows are only marked as such in the index until its segments are merged
+        sqlx::query("insert into notes_fts (notes_fts) values ('optimize')")
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::index("optimizing search index"))?;
+
+        tx.commit()
+            .await
+            .map_err(Error::index("committing search index transaction"))
+    }
+
+    /// Find the notes matching all of the words in `query`, best matches first.
+    ///
+    /// The last word is treated as a prefix, so that partially typed words still match.
+    pub(crate) async fn search(&self, query: &str, limit: u32) -> Result<Vec<SearchHit>> {
+        let Some(query) = fts_query(query) else {
+            return Ok(vec![]);
+        };
+
+        let rows = sqlx::query(
+            "select id, snippet(notes_fts, 1, '[', ']', '…', 12), bm25(notes_fts)
+            from notes_fts
+            where notes_fts match ?1
+            order by bm25(notes_fts)
+            limit ?2",
+        )
+        .bind(query)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::index("searching notes"))?;
+
+        rows.into_iter()
+            .map(|row| {
+                let id: String = row.get(0);
+                let id =
+                    uuid::Uuid::from_str(&id).map_err(Error::index("parsing indexed note id"))?;
+                Ok(SearchHit {
+                    id: RecordId(id),
+                    snippet: row.get(1),
+                    rank: row.get(2),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Turn free text into an FTS5 query, quoting every word so that FTS5 syntax characters are
+/// matched literally.
+fn fts_query(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+
+    if words.is_empty() {
+        return None;
+    }
+    Some(words.join(" ") + "*")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fts_query, SearchIndex};
+    use crate::test_util::{id, paris_note};
+
+    #[test]
+    fn query() {
+        assert_eq!(fts_query("  "), None);
+        assert_eq!(
+            fts_query(r#"hello "wor"#).as_deref(),
+            Some(r#""hello" """wor"*"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("search.db");
+        let index = SearchIndex::new(path.to_str().unwrap(), 1.0).await.unwrap();
+
+        let secret = paris_note("my password is hunter2");
+        index.insert(&[(id(1), secret.clone())]).await.unwrap();
+        assert_eq!(index.search("hunter2", 10).await.unwrap().len(), 1);
+
+        index.remove(&[id(1)]).await.unwrap();
+        assert!(index.search("hunter2", 10).await.unwrap().is_empty());
+        // removed notes are not added back
+        index.insert(&[(id(1), secret)]).await.unwrap();
+        assert!(index.search("hunter2", 10).await.unwrap().is_empty());
+
+        // nor is their text left behind in the database
+        sqlx::query("pragma wal_checkpoint(truncate)")
+            .execute(&index.pool)
+            .await
+            .unwrap();
+        let data = std::fs::read(&path).unwrap();
+        assert!(!data.windows(7).any(|bytes| bytes == b"hunter2"));
+    }
+}
@@ -0,0 +1,21 @@
+use std::{path::Path, str::FromStr, time::Duration};
+
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+
+/// Open (creating if needed) one of the local sqlite databases derived from the record store.
+pub(crate) async fn open(path: &str, timeout: f64) -> Result<SqlitePool, sqlx::Error> {
+    if let Some(dir) = Path::new(path).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let opts = SqliteConnectOptions::from_str(path)?
+        .journal_mode(SqliteJournalMode::Wal)
+        // removed notes should not be recoverable from free pages
+        .pragma("secure_delete", "on")
+        .create_if_missing(true);
+
+    SqlitePoolOptions::new()
+        .acquire_timeout(Duration::from_secs_f64(timeout))
+        .connect_with(opts)
+        .await
+}
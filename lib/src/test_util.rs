@@ -0,0 +1,54 @@
+//! Fixtures shared by the tests in this crate.
+
+use atuin_common::record::{HostId, RecordId};
+use jiff::{civil::datetime, tz::TimeZone, Zoned};
+use uuid::Uuid;
+
+use crate::Note;
+
+pub(crate) const HOST: HostId = HostId(Uuid::from_u128(1));
+pub(crate) const HOST2: HostId = HostId(Uuid::from_u128(2));
+
+/// A record id that is easy to read in assertions.
+pub(crate) fn id(n: u128) -> RecordId {
+    RecordId(Uuid::from_u128(n))
+}
+
+/// 2024-11-09 12:19:22 in Paris.
+pub(crate) fn paris() -> Zoned {
+    datetime(2024, 11, 9, 12, 19, 22, 0)
+        .to_zoned(TimeZone::get("Europe/Paris").unwrap())
+        .unwrap()
+}
+
+/// A note written at `datetime`, merged from the notes with the `children` ids.
+pub(crate) fn note_at(text: &str, datetime: Zoned, children: &[u128]) -> Note {
+    Note {
+        note: text.to_string(),
+        datetime,
+        children: children.iter().copied().map(id).collect(),
+        tags: vec![],
+        retracts: vec![],
+    }
+}
+
+/// A note written at `hour` on 2024-11-09 UTC, merged from the notes with the `children` ids.
+pub(crate) fn note(text: &str, hour: i8, children: &[u128]) -> Note {
+    let datetime = datetime(2024, 11, 9, hour, 0, 0, 0)
+        .to_zoned(TimeZone::UTC)
+        .unwrap();
+    note_at(text, datetime, children)
+}
+
+/// The note with `tags` added.
+pub(crate) fn tagged(note: Note, tags: &[&str]) -> Note {
+    Note {
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ..note
+    }
+}
+
+/// A note without children written at [`paris`].
+pub(crate) fn paris_note(text: &str) -> Note {
+    note_at(text, paris(), &[])
+}
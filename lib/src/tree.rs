@@ -0,0 +1,355 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use atuin_common::record::{HostId, RecordId};
+
+use crate::{Note, Query};
+
+/// The merge tree formed by notes and the children they were merged from.
+///
+/// A note is unprocessed until another note lists it as one of its children.
+/// Notes can be inserted in any order, and children may refer to notes that have not been inserted (yet).
+///
+/// Tombstones are not kept as notes, instead the notes they retract are removed from the tree.
+#[derive(Debug, Default, Clone)]
+pub struct NoteTree {
+    notes: BTreeMap<RecordId, (HostId, Note)>,
+    /// The notes that list each note as a child
+    parents: HashMap<RecordId, Vec<RecordId>>,
+    unprocessed: BTreeSet<RecordId>,
+    retracted: HashSet<RecordId>,
+}
+
+impl NoteTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a note written by `host`, as passed to [`Client::load_notes`](crate::Client::load_notes).
+    pub fn insert(&mut self, host: HostId, id: RecordId, note: Note) {
+        if note.is_tombstone() {
+            for &retracted in &note.retracts {
+                self.retract(retracted);
+            }
+            return;
+        }
+        // the tombstone might have been loaded before the note it retracts
+        if self.retracted.contains(&id) {
+            return;
+        }
+
+        // notes from other hosts are not loaded in order, so this note might already have been merged
+        if !self.parents.contains_key(&id) {
+            self.unprocessed.insert(id);
+        }
+        for child in &note.children {
+            self.unprocessed.remove(child);
+            let parents = self.parents.entry(*child).or_default();
+            if !parents.contains(&id) {
+                parents.push(id);
+            }
+        }
+        self.notes.insert(id, (host, note));
+    }
+
+    /// Remove a note from the tree, undoing the merge if it was a merge note.
+    fn retract(&mut self, id: RecordId) {
+        self.retracted.insert(id);
+        self.unprocessed.remove(&id);
+        let Some((_, note)) = self.notes.remove(&id) else {
+            return;
+        };
+
+        for child in &note.children {
+            let Some(parents) = self.parents.get_mut(child) else {
+                continue;
+            };
+            parents.retain(|parent| *parent != id);
+            if parents.is_empty() {
+                self.parents.remove(child);
+                if self.notes.contains_key(child) {
+                    self.unprocessed.insert(*child);
+                }
+            }
+        }
+    }
+
+    /// Whether the note was retracted by a tombstone.
+    pub fn is_retracted(&self, id: RecordId) -> bool {
+        self.retracted.contains(&id)
+    }
+
+    pub fn get(&self, id: RecordId) -> Option<&Note> {
+        self.notes.get(&id).map(|(_, note)| note)
+    }
+
+    /// The host that wrote the note.
+    pub fn host(&self, id: RecordId) -> Option<HostId> {
+        self.notes.get(&id).map(|(host, _)| *host)
+    }
+
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    /// All notes, oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (RecordId, &Note)> {
+        self.notes.iter().map(|(id, (_, note))| (*id, note))
+    }
+
+    /// Notes that have not been merged into another note, oldest first.
+    ///
+    /// These are the roots of the merge tree.
+    pub fn unprocessed(&self) -> impl DoubleEndedIterator<Item = RecordId> + '_ {
+        self.unprocessed.iter().copied()
+    }
+
+    /// Filter the notes in the tree, see [`Query`].
+    pub fn query(&self) -> Query<&Self> {
+        Query::new(self)
+    }
+
+    /// The notes that `id` was merged into.
+    pub fn parents_of(&self, id: RecordId) -> &[RecordId] {
+        self.parents.get(&id).map_or(&[], |parents| parents)
+    }
+
+    /// All notes merged into `id`, directly or indirectly, in depth-first order.
+    ///
+    /// Children that have not been loaded are included, and each note is only visited once.
+    pub fn descendants(&self, id: RecordId) -> Vec<RecordId> {
+        let mut seen = HashSet::from([id]);
+        let mut descendants = vec![];
+        let mut stack = self.children_of(id);
+        while let Some(id) = stack.pop() {
+            if seen.insert(id) {
+                descendants.push(id);
+                stack.extend(self.children_of(id));
+            }
+        }
+        descendants
+    }
+
+    /// How many merges deep the note is, see [`LevelPolicy`](crate::LevelPolicy).
+    ///
+    /// Notes that were not merged from anything are level 0, and merge notes are one level above
+    /// the highest of their children. Children that have not been loaded count as level 0.
+    pub fn level(&self, id: RecordId) -> Option<usize> {
+        self.notes
+            .contains_key(&id)
+            .then(|| self.level_inner(id, &mut HashMap::new(), &mut vec![]))
+    }
+
+    /// The level of every note, see [`NoteTree::level`].
+    pub fn levels(&self) -> HashMap<RecordId, usize> {
+        let mut levels = HashMap::new();
+        for &id in self.notes.keys() {
+            self.level_inner(id, &mut levels, &mut vec![]);
+        }
+        levels
+    }
+
+    fn level_inner(
+        &self,
+        id: RecordId,
+        levels: &mut HashMap<RecordId, usize>,
+        ancestors: &mut Vec<RecordId>,
+    ) -> usize {
+        if let Some(&level) = levels.get(&id) {
+            return level;
+        }
+        // cycles are not expanded again, like in `subtree`
+        if ancestors.contains(&id) {
+            return 0;
+        }
+        let Some((_, note)) = self.notes.get(&id) else {
+            return 0;
+        };
+
+        ancestors.push(id);
+        let level = note
+            .children
+            .iter()
+            .map(|&child| self.level_inner(child, levels, ancestors))
+            .max()
+            .map_or(0, |level| level + 1);
+        ancestors.pop();
+
+        levels.insert(id, level);
+        level
+    }
+
+    /// Resolve the note `id` and, recursively, the notes it was merged from.
+    pub fn subtree(&self, id: RecordId) -> Subtree<'_> {
+        self.subtree_inner(id, &mut vec![])
+    }
+
+    fn subtree_inner(&self, id: RecordId, ancestors: &mut Vec<RecordId>) -> Subtree<'_> {
+        if ancestors.contains(&id) {
+            return Subtree::Cycle(id);
+        }
+        if self.retracted.contains(&id) {
+            return Subtree::Retracted(id);
+        }
+        let Some((host, note)) = self.notes.get(&id) else {
+            return Subtree::Missing(id);
+        };
+
+        ancestors.push(id);
+        let children = note
+            .children
+            .iter()
+            .map(|child| self.subtree_inner(*child, ancestors))
+            .collect();
+        ancestors.pop();
+
+        Subtree::Note {
+            id,
+            host: *host,
+            note,
+            children,
+        }
+    }
+
+    /// The children of `id`, reversed so they can be popped off a stack in order.
+    fn children_of(&self, id: RecordId) -> Vec<RecordId> {
+        self.get(id)
+            .map_or(vec![], |note| note.children.iter().rev().copied().collect())
+    }
+}
+
+/// A note and the notes it was merged from, see [`NoteTree::subtree`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subtree<'a> {
+    Note {
+        id: RecordId,
+        host: HostId,
+        note: &'a Note,
+        children: Vec<Subtree<'a>>,
+    },
+    /// The note has not been loaded, e.g. because its host has not synced yet.
+    Missing(RecordId),
+    /// The note was retracted, see [`Client::retract`](crate::Client::retract).
+    Retracted(RecordId),
+    /// The note is one of its own ancestors, so it is not expanded again.
+    Cycle(RecordId),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NoteTree, Subtree};
+    use crate::{
+        test_util::{id, note, HOST, HOST2},
+        Note,
+    };
+
+    fn tombstone(retracts: &[u128]) -> Note {
+        Note {
+            retracts: retracts.iter().copied().map(id).collect(),
+            ..note("", 12, &[])
+        }
+    }
+
+    #[test]
+    fn tree() {
+        let mut tree = NoteTree::new();
+        tree.insert(HOST, id(1), note("note", 12, &[]));
+        tree.insert(HOST, id(2), note("note", 12, &[]));
+        tree.insert(HOST, id(3), note("note", 12, &[]));
+        tree.insert(HOST, id(4), note("note", 12, &[1, 2]));
+        tree.insert(HOST, id(5), note("note", 12, &[4, 9]));
+
+        assert_eq!(tree.unprocessed().collect::<Vec<_>>(), [id(3), id(5)]);
+        assert_eq!(tree.parents_of(id(1)), [id(4)]);
+        assert_eq!(tree.parents_of(id(5)), []);
+        assert_eq!(tree.descendants(id(5)), [id(4), id(1), id(2), id(9)]);
+        assert_eq!(tree.host(id(4)), Some(HOST));
+        assert_eq!(tree.host(id(9)), None);
+
+        assert_eq!(tree.level(id(3)), Some(0));
+        assert_eq!(tree.level(id(4)), Some(1));
+        assert_eq!(tree.level(id(5)), Some(2));
+        assert_eq!(tree.level(id(9)), None);
+        assert_eq!(tree.levels().len(), 5);
+    }
+
+    #[test]
+    fn children_after_parents() {
+        let mut tree = NoteTree::new();
+        // HOST2 merged notes from HOST, but its records are loaded first
+        tree.insert(HOST2, id(3), note("note", 12, &[1, 2]));
+        tree.insert(HOST2, id(4), note("note", 12, &[]));
+        assert_eq!(tree.unprocessed().collect::<Vec<_>>(), [id(3), id(4)]);
+
+        tree.insert(HOST, id(1), note("note", 12, &[]));
+        tree.insert(HOST, id(2), note("note", 12, &[]));
+        tree.insert(HOST, id(5), note("note", 12, &[]));
+
+        assert_eq!(
+            tree.unprocessed().collect::<Vec<_>>(),
+            [id(3), id(4), id(5)]
+        );
+        assert_eq!(tree.parents_of(id(2)), [id(3)]);
+        assert_eq!(tree.host(id(1)), Some(HOST));
+    }
+
+    #[test]
+    fn subtree() {
+        let mut tree = NoteTree::new();
+        let (note1, note2, note3) = (
+            note("note", 12, &[3]),
+            note("note", 12, &[1, 9]),
+            note("note", 12, &[2]),
+        );
+        tree.insert(HOST, id(1), note1.clone());
+        tree.insert(HOST2, id(2), note2.clone());
+        tree.insert(HOST, id(3), note3.clone());
+
+        assert_eq!(
+            tree.subtree(id(2)),
+            Subtree::Note {
+                id: id(2),
+                host: HOST2,
+                note: &note2,
+                children: vec![
+                    Subtree::Note {
+                        id: id(1),
+                        host: HOST,
+                        note: &note1,
+                        children: vec![Subtree::Note {
+                            id: id(3),
+                            host: HOST,
+                            note: &note3,
+                            children: vec![Subtree::Cycle(id(2))],
+                        }],
+                    },
+                    Subtree::Missing(id(9)),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn retract() {
+        let mut tree = NoteTree::new();
+        tree.insert(HOST, id(1), note("note", 12, &[]));
+        tree.insert(HOST, id(2), note("note", 12, &[]));
+        tree.insert(HOST, id(3), note("note", 12, &[1, 2]));
+        // retracting a merge makes its children unprocessed again
+        tree.insert(HOST, id(4), tombstone(&[3, 2]));
+        // the tombstone for this note was loaded first
+        tree.insert(HOST2, id(5), tombstone(&[6]));
+        tree.insert(HOST, id(6), note("note", 12, &[]));
+
+        assert_eq!(tree.unprocessed().collect::<Vec<_>>(), [id(1)]);
+        assert_eq!(tree.parents_of(id(1)), []);
+        assert_eq!(tree.get(id(6)), None);
+        assert!(tree.is_retracted(id(2)));
+        assert!(!tree.is_retracted(id(4)));
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree.subtree(id(3)), Subtree::Retracted(id(3)));
+    }
+}
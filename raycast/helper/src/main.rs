@@ -1,16 +1,34 @@
-use std::{io, path::PathBuf};
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    process,
+};
 
 use clap::Parser;
-use jiff::Zoned;
-use note_lsm_lib::Client;
-// use comfy_table::Table;
-use serde::{Deserialize, Serialize};
+use comfy_table::Table;
+use eyre::{eyre, Context};
+use jiff::{civil::DateTime, tz::TimeZone, Span, Zoned};
+use note_lsm_lib::{
+    from_jsonl, plan_rollups, read_daily_notes, to_jsonl, to_markdown, Client, CompactOptions,
+    Config, ExportOptions, HostId, Note, NoteTree, Query, RecordId, RollupPeriod, Settings,
+    SkippedRecord, Subtree,
+};
+use serde::Serialize;
 
 #[derive(clap::Parser, Debug)]
 struct Args {
+    /// The note-lsm config file, defaults to $XDG_CONFIG_HOME/note-lsm/config.toml
     #[arg(short, long)]
     config: Option<PathBuf>,
 
+    /// The profile from the config file to use
+    #[arg(short, long)]
+    profile: Option<String>,
+
+    /// The notebook to read and write notes in
+    #[arg(long, default_value = note_lsm_lib::DEFAULT_NOTEBOOK)]
+    notebook: String,
+
     #[arg(short, long, default_value = "text")]
     output: Output,
 
@@ -21,11 +39,194 @@ struct Args {
 #[derive(clap::Subcommand, Debug)]
 enum Command {
     Record(RecordArgs),
+    /// List notes that have not been merged into another note, newest first
+    List(ListArgs),
+    /// Merge notes into a new note that summarises them
+    Merge(MergeArgs),
+    /// Show a note and, recursively, the notes it was merged from
+    Show(ShowArgs),
+    /// Hide notes from lists, search and exports
+    ///
+    /// The notes are not deleted, they remain encrypted in the record store.
+    Retract(RetractArgs),
+    /// Delete notes from the local store once they have been merged for a while
+    ///
+    /// The merge note is kept, the notes merged into it are shown as missing.
+    Compact(CompactArgs),
+    /// Merge the unprocessed notes of each past day or week into a note for that day or week
+    Rollup(RollupArgs),
+    /// Export notes to stdout
+    Export(ExportArgs),
+    /// Import notes from a JSON Lines export, skipping notes that already exist
+    Import(ImportArgs),
+    /// Import the bullets of a directory of `YYYY-MM-DD.md` daily notes as notes
+    ImportDaily(ImportDailyArgs),
+    /// Search the text and tags of all notes
+    Search(SearchArgs),
+    /// List all tags used on notes, with the number of notes using each
+    Tags,
+    /// Count the notes at each level of the merge tree, and check them against their budgets
+    ///
+    /// Quick notes are level 0, and merge notes are one level above the notes merged into them.
+    Stats,
+    /// List note records that could not be read, e.g. due to a key mismatch
+    Skipped,
+    /// List notebooks, or create a new one
+    Notebooks(NotebooksArgs),
+}
+
+#[derive(clap::Parser, Debug)]
+struct NotebooksArgs {
+    #[command(subcommand)]
+    command: Option<NotebooksCommand>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum NotebooksCommand {
+    /// Create a notebook, so that it is listed before it has any notes
+    Create { name: String },
 }
 
 #[derive(clap::Parser, Debug)]
 struct RecordArgs {
-    note: String,
+    /// The text of the note, or `-` to read it from stdin
+    #[arg(required_unless_present = "file")]
+    note: Option<String>,
+
+    /// Read the text of the note from a file
+    #[arg(short, long, conflicts_with = "note")]
+    file: Option<PathBuf>,
+
+    /// Tag the note, can be given multiple times
+    #[arg(short, long = "tag")]
+    tags: Vec<String>,
+}
+
+#[derive(clap::Parser, Debug)]
+struct ListArgs {
+    /// Only list notes written at or after this local date or date-time, e.g. `2024-11-09`
+    #[arg(long)]
+    since: Option<DateTime>,
+
+    /// Only list notes written by this atuin host id
+    #[arg(long)]
+    host: Option<uuid::Uuid>,
+
+    /// Maximum number of notes to list
+    #[arg(short, long)]
+    limit: Option<usize>,
+}
+
+#[derive(clap::Parser, Debug)]
+struct MergeArgs {
+    /// The ids of the notes to merge
+    #[arg(required = true)]
+    ids: Vec<uuid::Uuid>,
+
+    /// The text of the merged note
+    #[arg(short, long, required_unless_present = "editor")]
+    note: Option<String>,
+
+    /// Write the merged note in $EDITOR, starting from the text of the merged notes
+    #[arg(short, long, conflicts_with = "note")]
+    editor: bool,
+
+    /// Tag the merged note, can be given multiple times
+    #[arg(short, long = "tag")]
+    tags: Vec<String>,
+}
+
+#[derive(clap::Parser, Debug)]
+struct ShowArgs {
+    id: uuid::Uuid,
+}
+
+#[derive(clap::Parser, Debug)]
+struct ExportArgs {
+    #[arg(short, long, default_value = "markdown")]
+    format: ExportFormat,
+
+    /// Only export this note and the notes merged into it, Markdown only
+    #[arg(long)]
+    root: Option<uuid::Uuid>,
+
+    /// Only export notes written at or after this local date or date-time
+    #[arg(long)]
+    since: Option<DateTime>,
+
+    /// Only export notes written before this local date or date-time
+    #[arg(long)]
+    until: Option<DateTime>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum ExportFormat {
+    Markdown,
+    /// One JSON object per note, for backups and `import`
+    Jsonl,
+}
+
+#[derive(clap::Parser, Debug)]
+struct RetractArgs {
+    #[arg(required = true)]
+    ids: Vec<uuid::Uuid>,
+}
+
+#[derive(clap::Parser, Debug)]
+#[group(required = true, multiple = true)]
+struct RollupArgs {
+    /// Merge notes into a note per day
+    #[arg(long)]
+    daily: bool,
+
+    /// Merge notes, including daily rollups, into a note per week
+    #[arg(long)]
+    weekly: bool,
+}
+
+#[derive(clap::Parser, Debug)]
+struct CompactArgs {
+    /// Only compact merge notes written at least this many days ago
+    #[arg(long, default_value_t = 30)]
+    days: u32,
+
+    /// Also delete the notes on other hosts when they next sync
+    #[arg(long, conflicts_with = "dry_run")]
+    propagate: bool,
+
+    /// List the notes that would be deleted, without deleting them
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(clap::Parser, Debug)]
+struct ImportArgs {
+    /// The file to import, or `-` to read from stdin
+    file: PathBuf,
+}
+
+#[derive(clap::Parser, Debug)]
+struct ImportDailyArgs {
+    /// The directory to search for daily notes
+    dir: PathBuf,
+
+    /// Also add a note for each day, with that day's bullets merged into it
+    #[arg(long)]
+    merge: bool,
+
+    /// The time zone the daily notes were written in, defaults to the system time zone
+    #[arg(long)]
+    time_zone: Option<String>,
+}
+
+#[derive(clap::Parser, Debug)]
+struct SearchArgs {
+    #[arg(required = true)]
+    query: Vec<String>,
+
+    /// Maximum number of results to return
+    #[arg(short, long, default_value_t = 20)]
+    limit: u32,
 }
 
 #[derive(clap::ValueEnum, Default, Clone, Copy, Debug)]
@@ -35,61 +236,839 @@ enum Output {
     Json,
 }
 
+/// Sync the record store if sync is enabled.
+///
+/// The notes in the local store can still be used, so a failed sync is only reported.
+async fn sync(client: &mut Client, settings: &Settings) -> eyre::Result<()> {
+    if settings.should_sync()? {
+        if let Err(err) = client.sync(settings).await {
+            eprintln!("warning: sync failed: {:#}", eyre::Report::from(err));
+        }
+    }
+    Ok(())
+}
+
 #[tokio::main(flavor = "current_thread")]
-async fn main() {
+async fn main() -> eyre::Result<()> {
     let args = Args::parse();
 
-    let mut settings = note_lsm_lib::Settings::new().unwrap();
-
-    settings.record_store_path = PathBuf::from(std::env::var_os("HOME").expect("$HOME not found"))
-        .join(".local")
-        .join("share")
-        .join("notelsm")
-        .join("store.db")
-        .to_str()
-        .unwrap()
-        .to_owned();
+    let config = Config::load(args.config.as_deref(), args.profile.as_deref())?;
+    let settings = config.settings()?;
 
-    let mut client = Client::new(&settings).await;
+    let mut client = Client::with_notebook(&settings, &args.notebook).await?;
 
     let output: Box<dyn EncodeOutput> = match args.command {
         Command::Record(record_args) => {
-            println!("adding {:?}", record_args.note);
+            let note = match (record_args.note, record_args.file) {
+                (Some(note), _) if note == "-" => {
+                    io::read_to_string(io::stdin()).context("reading note from stdin")?
+                }
+                (Some(note), _) => note,
+                (None, Some(file)) => std::fs::read_to_string(&file)
+                    .with_context(|| format!("reading note from {}", file.display()))?,
+                (None, None) => unreachable!("clap requires a note or a file"),
+            };
+            // piped input and files usually end in a newline
+            let note = note.trim_end().to_owned();
+            if note.is_empty() {
+                return Err(eyre!("note is empty"));
+            }
+
+            let datetime = Zoned::now();
+            let id = client
+                .add_record(Note {
+                    note,
+                    datetime: datetime.clone(),
+                    children: vec![],
+                    tags: record_args.tags,
+                    retracts: vec![],
+                })
+                .await?;
 
+            // the note is already saved locally, so a failed sync should not fail the command
+            let sync = if settings.should_sync()? {
+                match client.sync(&settings).await {
+                    Ok(summary) => SyncResult::Synced {
+                        uploaded: summary.uploaded,
+                        downloaded: summary.downloaded,
+                    },
+                    Err(err) => SyncResult::Failed {
+                        error: format!("{:#}", eyre::Report::from(err)),
+                    },
+                }
+            } else {
+                SyncResult::Disabled
+            };
+
+            Box::new(RecordOutput {
+                id,
+                datetime: datetime.to_string(),
+                host: client.host_id(),
+                sync,
+            })
+        }
+        Command::List(list_args) => {
+            sync(&mut client, &settings).await?;
+
+            let mut tree = NoteTree::new();
+            client
+                .load_notes(|host, id, note| tree.insert(host, id, note))
+                .await?;
+
+            let since = list_args
+                .since
+                .map(|since| since.to_zoned(TimeZone::system()))
+                .transpose()?;
+
+            let notes = tree
+                .query()
+                .unprocessed()
+                .since(since.map(|since| since.timestamp()))
+                .host(list_args.host.map(HostId))
+                .iter()
+                .rev()
+                .take(list_args.limit.unwrap_or(usize::MAX))
+                .map(|(host, id, note)| ListNote {
+                    id,
+                    host,
+                    datetime: note.datetime.to_string(),
+                    local_time: local_time(&note.datetime),
+                    note: note.note.clone(),
+                    tags: note.tags.clone(),
+                })
+                .collect();
+            Box::new(ListOutput(notes))
+        }
+        Command::Merge(merge_args) => {
+            sync(&mut client, &settings).await?;
+
+            let mut tree = NoteTree::new();
+            client
+                .load_notes(|host, id, note| tree.insert(host, id, note))
+                .await?;
+
+            let children: Vec<RecordId> = merge_args.ids.into_iter().map(RecordId).collect();
+            let mut texts = vec![];
+            for &id in &children {
+                let note = tree
+                    .get(id)
+                    .ok_or_else(|| eyre!("note {} not found", id.0))?;
+                texts.push(note.note.as_str());
+            }
+
+            let note = match merge_args.note {
+                Some(note) => note,
+                None => edit(&texts.join("\n\n"))?,
+            };
+            if note.trim().is_empty() {
+                return Err(eyre!("merged note is empty, aborting"));
+            }
+
+            let id = client
+                .add_record(Note {
+                    note,
+                    datetime: Zoned::now(),
+                    children,
+                    tags: merge_args.tags,
+                    retracts: vec![],
+                })
+                .await?;
+            sync(&mut client, &settings).await?;
+
+            Box::new(MergeOutput { id })
+        }
+        Command::Retract(retract_args) => {
+            sync(&mut client, &settings).await?;
+
+            let mut tree = NoteTree::new();
             client
-                .add_record(record_args.note, vec![], Zoned::now())
-                .await;
-            if settings.should_sync().unwrap() {
-                client.sync(&settings).await;
+                .load_notes(|host, id, note| tree.insert(host, id, note))
+                .await?;
+
+            let ids: Vec<RecordId> = retract_args.ids.into_iter().map(RecordId).collect();
+            for &id in &ids {
+                if tree.get(id).is_none() {
+                    return Err(eyre!("note {} not found", id.0));
+                }
             }
 
-            Box::new(NoOutput {})
+            let id = client.retract(&ids).await?;
+            sync(&mut client, &settings).await?;
+
+            Box::new(RetractOutput { tombstone: id })
+        }
+        Command::Rollup(rollup_args) => {
+            sync(&mut client, &settings).await?;
+
+            let mut tree = NoteTree::new();
+            client
+                .load_notes(|host, id, note| tree.insert(host, id, note))
+                .await?;
+
+            let periods = [
+                (rollup_args.daily, RollupPeriod::Daily),
+                (rollup_args.weekly, RollupPeriod::Weekly),
+            ];
+            let now = Zoned::now();
+            let mut rollups = vec![];
+            for (_, period) in periods.into_iter().filter(|(enabled, _)| *enabled) {
+                for note in plan_rollups(&tree, period, client.host_id(), &now) {
+                    let id = client.add_record(note.clone()).await?;
+                    rollups.push(RollupNote {
+                        id,
+                        title: note.note.clone(),
+                        children: note.children.len(),
+                    });
+                    // so that weekly rollups include the new daily rollups
+                    tree.insert(client.host_id(), id, note);
+                }
+            }
+            sync(&mut client, &settings).await?;
+
+            Box::new(RollupOutput(rollups))
+        }
+        Command::Compact(compact_args) => {
+            sync(&mut client, &settings).await?;
+
+            let options = CompactOptions {
+                min_age: Span::new().try_days(compact_args.days)?,
+                propagate: compact_args.propagate,
+                dry_run: compact_args.dry_run,
+            };
+            let summary = client.compact(&options).await?;
+            if !compact_args.dry_run {
+                sync(&mut client, &settings).await?;
+            }
+
+            Box::new(CompactOutput {
+                dry_run: compact_args.dry_run,
+                merges: summary.merges,
+                deleted: summary.deleted,
+            })
+        }
+        Command::Show(show_args) => {
+            sync(&mut client, &settings).await?;
+
+            let mut tree = NoteTree::new();
+            client
+                .load_notes(|host, id, note| tree.insert(host, id, note))
+                .await?;
+
+            let node = match tree.subtree(RecordId(show_args.id)) {
+                Subtree::Missing(id) => return Err(eyre!("note {} not found", id.0)),
+                Subtree::Retracted(id) => return Err(eyre!("note {} was retracted", id.0)),
+                subtree => ShowNode::from(subtree),
+            };
+            Box::new(ShowOutput(node))
+        }
+        Command::Export(export_args) => {
+            sync(&mut client, &settings).await?;
+
+            let local = |datetime: DateTime| datetime.to_zoned(TimeZone::system());
+            let options = ExportOptions {
+                root: export_args.root.map(RecordId),
+                since: export_args
+                    .since
+                    .map(local)
+                    .transpose()?
+                    .map(|z| z.timestamp()),
+                until: export_args
+                    .until
+                    .map(local)
+                    .transpose()?
+                    .map(|z| z.timestamp()),
+            };
+
+            let exported = match export_args.format {
+                ExportFormat::Markdown => {
+                    let mut tree = NoteTree::new();
+                    client
+                        .load_notes(|host, id, note| tree.insert(host, id, note))
+                        .await?;
+                    to_markdown(&tree, &options)
+                }
+                ExportFormat::Jsonl => {
+                    if options.root.is_some() {
+                        return Err(eyre!("--root is only supported for Markdown exports"));
+                    }
+                    let records = client.records().await?;
+                    let records: Vec<_> = Query::records(&records)
+                        .since(options.since)
+                        .until(options.until)
+                        .iter()
+                        .cloned()
+                        .collect();
+                    to_jsonl(&records)
+                }
+            };
+            Box::new(ExportOutput(exported))
+        }
+        Command::Import(import_args) => {
+            let input = if import_args.file.as_os_str() == "-" {
+                io::read_to_string(io::stdin()).context("reading import from stdin")?
+            } else {
+                std::fs::read_to_string(&import_args.file).with_context(|| {
+                    format!("reading import from {}", import_args.file.display())
+                })?
+            };
+            let notes = from_jsonl(&input)?;
+
+            // sync first, so that notes restored on another host are not imported twice
+            sync(&mut client, &settings).await?;
+            let imported = client.import(notes).await?;
+            sync(&mut client, &settings).await?;
+
+            Box::new(ImportOutput(imported))
+        }
+        Command::ImportDaily(import_args) => {
+            let tz = match &import_args.time_zone {
+                Some(name) => {
+                    TimeZone::get(name).with_context(|| format!("unknown time zone {name:?}"))?
+                }
+                None => TimeZone::system(),
+            };
+
+            let mut notes = vec![];
+            for daily in read_daily_notes(&import_args.dir, &tz)? {
+                let merge = import_args.merge.then(|| daily.merge_note()).flatten();
+                notes.extend(daily.notes);
+                notes.extend(merge);
+            }
+
+            // the note ids are derived from the files, so notes imported before are skipped
+            sync(&mut client, &settings).await?;
+            let imported = client.import(notes).await?;
+            sync(&mut client, &settings).await?;
+
+            Box::new(ImportOutput(imported))
+        }
+        Command::Search(search_args) => {
+            sync(&mut client, &settings).await?;
+
+            client.load_notes(|_, _, _| {}).await?;
+
+            let hits = client
+                .search(&search_args.query.join(" "), search_args.limit)
+                .await?
+                .into_iter()
+                .map(|hit| SearchHit {
+                    id: hit.id,
+                    snippet: hit.snippet,
+                    rank: hit.rank,
+                })
+                .collect();
+            Box::new(SearchOutput(hits))
+        }
+        Command::Stats => {
+            sync(&mut client, &settings).await?;
+
+            let mut tree = NoteTree::new();
+            client
+                .load_notes(|host, id, note| tree.insert(host, id, note))
+                .await?;
+
+            let levels = config
+                .levels
+                .stats(&tree)
+                .into_iter()
+                .map(|stats| Level {
+                    over_budget: stats.over_budget(),
+                    level: stats.level,
+                    notes: stats.notes,
+                    unprocessed: stats.unprocessed,
+                    budget: stats.budget,
+                })
+                .collect();
+            Box::new(StatsOutput(levels))
+        }
+        Command::Tags => {
+            sync(&mut client, &settings).await?;
+
+            client.load_notes(|_, _, _| {}).await?;
+
+            let tags = client
+                .tags()
+                .iter()
+                .map(|(tag, &count)| Tag {
+                    tag: tag.clone(),
+                    count,
+                })
+                .collect();
+            Box::new(TagsOutput(tags))
+        }
+        Command::Notebooks(notebooks_args) => {
+            if let Some(NotebooksCommand::Create { name }) = notebooks_args.command {
+                client.create_notebook(&name).await?;
+            }
+            sync(&mut client, &settings).await?;
+
+            Box::new(NotebooksOutput(client.notebooks().await?))
+        }
+        Command::Skipped => {
+            sync(&mut client, &settings).await?;
+
+            let skipped = client.load_notes(|_, _, _| {}).await?;
+
+            Box::new(SkippedOutput(
+                skipped.into_iter().map(Skipped::from).collect(),
+            ))
         }
     };
 
     let mut stdout = io::stdout();
-    output.encode(args.output, &mut stdout).unwrap();
+    output.encode(args.output, &mut stdout)?;
+
+    Ok(())
+}
+
+/// Format a note time for text output, in the local time zone.
+fn local_time(datetime: &Zoned) -> String {
+    datetime
+        .with_time_zone(TimeZone::system())
+        .strftime("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// Let the user edit `text` in `$EDITOR`, returning the edited text.
+fn edit(text: &str) -> eyre::Result<String> {
+    let mut file = tempfile::Builder::new()
+        .suffix(".md")
+        .tempfile()
+        .context("creating file to edit")?;
+    file.write_all(text.as_bytes())
+        .context("writing file to edit")?;
+
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_owned());
+    // $EDITOR may include arguments, e.g. `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().ok_or_else(|| eyre!("$EDITOR is empty"))?;
+    let status = process::Command::new(program)
+        .args(words)
+        .arg(file.path())
+        .status()
+        .with_context(|| format!("running editor {editor:?}"))?;
+    if !status.success() {
+        return Err(eyre!("editor {editor:?} exited with {status}"));
+    }
+
+    let text = std::fs::read_to_string(file.path()).context("reading edited file")?;
+    // editors usually add a trailing newline
+    Ok(text.trim_end().to_owned())
 }
 
 trait EncodeOutput {
     fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()>;
 }
 
-struct NoOutput {}
+#[derive(Serialize)]
+struct RecordOutput {
+    id: RecordId,
+    datetime: String,
+    host: HostId,
+    sync: SyncResult,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum SyncResult {
+    /// Syncing is turned off in the atuin settings.
+    Disabled,
+    Synced {
+        uploaded: u64,
+        downloaded: u64,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+impl EncodeOutput for RecordOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text => {
+                writeln!(w, "{}", self.id.0)?;
+                if let SyncResult::Failed { error } = &self.sync {
+                    writeln!(w, "sync failed: {error}")?;
+                }
+                Ok(())
+            }
+            Output::Json => json(&*self, w),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct MergeOutput {
+    id: RecordId,
+}
+
+impl EncodeOutput for MergeOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text => writeln!(w, "{}", self.id.0),
+            Output::Json => json(&*self, w),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RetractOutput {
+    tombstone: RecordId,
+}
+
+impl EncodeOutput for RetractOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text => writeln!(w, "{}", self.tombstone.0),
+            Output::Json => json(&*self, w),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RollupNote {
+    id: RecordId,
+    title: String,
+    children: usize,
+}
+
+struct RollupOutput(Vec<RollupNote>);
+
+impl EncodeOutput for RollupOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text => {
+                for rollup in &self.0 {
+                    writeln!(
+                        w,
+                        "{}\t{} ({} notes)",
+                        rollup.id.0, rollup.title, rollup.children
+                    )?;
+                }
+                Ok(())
+            }
+            Output::Json => json(&self.0, w),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CompactOutput {
+    dry_run: bool,
+    merges: Vec<RecordId>,
+    deleted: Vec<RecordId>,
+}
+
+impl EncodeOutput for CompactOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text if self.dry_run => {
+                writeln!(
+                    w,
+                    "would delete {} notes merged into {} notes",
+                    self.deleted.len(),
+                    self.merges.len()
+                )?;
+                for id in &self.deleted {
+                    writeln!(w, "{}", id.0)?;
+                }
+                Ok(())
+            }
+            Output::Text => writeln!(
+                w,
+                "deleted {} notes merged into {} notes",
+                self.deleted.len(),
+                self.merges.len()
+            ),
+            Output::Json => json(&*self, w),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ShowNode {
+    Note {
+        id: RecordId,
+        host: HostId,
+        datetime: String,
+        #[serde(skip)]
+        local_time: String,
+        note: String,
+        tags: Vec<String>,
+        children: Vec<ShowNode>,
+    },
+    /// The note has not been synced to this machine.
+    Missing { id: RecordId },
+    /// The note was already shown as an ancestor.
+    Cycle { id: RecordId },
+    /// The note was retracted.
+    Retracted { id: RecordId },
+}
+
+impl From<Subtree<'_>> for ShowNode {
+    fn from(subtree: Subtree<'_>) -> Self {
+        match subtree {
+            Subtree::Note {
+                id,
+                host,
+                note,
+                children,
+            } => Self::Note {
+                id,
+                host,
+                datetime: note.datetime.to_string(),
+                local_time: local_time(&note.datetime),
+                note: note.note.clone(),
+                tags: note.tags.clone(),
+                children: children.into_iter().map(Self::from).collect(),
+            },
+            Subtree::Missing(id) => Self::Missing { id },
+            Subtree::Cycle(id) => Self::Cycle { id },
+            Subtree::Retracted(id) => Self::Retracted { id },
+        }
+    }
+}
+
+impl ShowNode {
+    fn write_text(&self, depth: usize, w: &mut dyn io::Write) -> io::Result<()> {
+        let indent = "    ".repeat(depth);
+        match self {
+            ShowNode::Note {
+                id,
+                host,
+                local_time,
+                note,
+                children,
+                ..
+            } => {
+                writeln!(w, "{indent}{} {local_time} (host {})", id.0, host.0)?;
+                for line in note.lines() {
+                    writeln!(w, "{indent}  {line}")?;
+                }
+                for child in children {
+                    child.write_text(depth + 1, w)?;
+                }
+                Ok(())
+            }
+            ShowNode::Missing { id } => writeln!(w, "{indent}{} (missing)", id.0),
+            ShowNode::Cycle { id } => writeln!(w, "{indent}{} (cycle)", id.0),
+            ShowNode::Retracted { id } => writeln!(w, "{indent}{} (retracted)", id.0),
+        }
+    }
+}
+
+struct ShowOutput(ShowNode);
+
+impl EncodeOutput for ShowOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text => self.0.write_text(0, w),
+            Output::Json => json(&self.0, w),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Skipped {
+    id: RecordId,
+    host: HostId,
+    idx: u64,
+    reason: String,
+}
+
+impl From<SkippedRecord> for Skipped {
+    fn from(skipped: SkippedRecord) -> Self {
+        Self {
+            id: skipped.id,
+            host: skipped.host,
+            idx: skipped.idx,
+            reason: format!("{:#}", eyre::Report::from(skipped.reason)),
+        }
+    }
+}
+
+struct SkippedOutput(Vec<Skipped>);
+
+impl EncodeOutput for SkippedOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text => {
+                for skipped in &self.0 {
+                    writeln!(
+                        w,
+                        "{} (host {}, idx {}): {}",
+                        skipped.id.0, skipped.host.0, skipped.idx, skipped.reason
+                    )?;
+                }
+                Ok(())
+            }
+            Output::Json => json(&self.0, w),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ListNote {
+    id: RecordId,
+    host: HostId,
+    datetime: String,
+    #[serde(skip)]
+    local_time: String,
+    note: String,
+    tags: Vec<String>,
+}
+
+struct ListOutput(Vec<ListNote>);
+
+impl EncodeOutput for ListOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text => {
+                let mut table = Table::new();
+                table.load_preset(comfy_table::presets::NOTHING);
+                table.set_header(["id", "time", "note"]);
+                for note in &self.0 {
+                    table.add_row([
+                        note.id.0.to_string(),
+                        note.local_time.clone(),
+                        note.note.clone(),
+                    ]);
+                }
+                writeln!(w, "{table}")
+            }
+            Output::Json => json(&self.0, w),
+        }
+    }
+}
 
-impl EncodeOutput for NoOutput {
+#[derive(Serialize)]
+struct SearchHit {
+    id: RecordId,
+    snippet: String,
+    rank: f64,
+}
+
+struct SearchOutput(Vec<SearchHit>);
+
+impl EncodeOutput for SearchOutput {
     fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
         match method {
-            Output::Text => Ok(()),
-            Output::Json => json(&serde_json::Value::Null, w),
+            Output::Text => {
+                for hit in &self.0 {
+                    writeln!(w, "{}\t{}", hit.id.0, hit.snippet)?;
+                }
+                Ok(())
+            }
+            Output::Json => json(&self.0, w),
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// An export in a format chosen with `--format`, written as is.
+struct ExportOutput(String);
+
+impl EncodeOutput for ExportOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text => w.write_all(self.0.as_bytes()),
+            Output::Json => json(&self.0, w),
+        }
+    }
+}
+
+struct ImportOutput(Vec<RecordId>);
+
+impl EncodeOutput for ImportOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text => {
+                for id in &self.0 {
+                    writeln!(w, "{}", id.0)?;
+                }
+                Ok(())
+            }
+            Output::Json => json(&self.0, w),
+        }
+    }
+}
+
+struct NotebooksOutput(Vec<String>);
+
+impl EncodeOutput for NotebooksOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text => {
+                for notebook in &self.0 {
+                    writeln!(w, "{notebook}")?;
+                }
+                Ok(())
+            }
+            Output::Json => json(&self.0, w),
+        }
+    }
+}
+
+#[derive(Serialize)]
 struct Tag {
     tag: String,
-    description: Option<String>,
+    count: usize,
+}
+
+struct TagsOutput(Vec<Tag>);
+
+impl EncodeOutput for TagsOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text => {
+                for tag in &self.0 {
+                    writeln!(w, "{}\t{}", tag.tag, tag.count)?;
+                }
+                Ok(())
+            }
+            Output::Json => json(&self.0, w),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Level {
+    level: usize,
+    notes: usize,
+    unprocessed: usize,
+    budget: Option<usize>,
+    over_budget: bool,
+}
+
+struct StatsOutput(Vec<Level>);
+
+impl EncodeOutput for StatsOutput {
+    fn encode(self: Box<Self>, method: Output, w: &mut dyn io::Write) -> io::Result<()> {
+        match method {
+            Output::Text => {
+                let mut table = Table::new();
+                table.load_preset(comfy_table::presets::NOTHING);
+                table.set_header(["level", "notes", "unprocessed", "budget", ""]);
+                for level in &self.0 {
+                    table.add_row([
+                        level.level.to_string(),
+                        level.notes.to_string(),
+                        level.unprocessed.to_string(),
+                        level
+                            .budget
+                            .map_or("-".to_owned(), |budget| budget.to_string()),
+                        if level.over_budget {
+                            "over budget, time to merge".to_owned()
+                        } else {
+                            String::new()
+                        },
+                    ]);
+                }
+                writeln!(w, "{table}")
+            }
+            Output::Json => json(&self.0, w),
+        }
+    }
 }
 
 pub fn json<T>(value: &T, w: &mut dyn io::Write) -> io::Result<()>
@@ -1,4 +1,4 @@
-import { LaunchProps, getPreferenceValues } from "@raycast/api";
+import { LaunchProps, getPreferenceValues, showHUD } from "@raycast/api";
 import { execFile } from "child_process";
 import { promisify } from "util";
 
@@ -13,10 +13,32 @@ interface Preferences {
   cmdPath: string,
 }
 
+type SyncResult =
+  | { status: "disabled" }
+  | { status: "synced", uploaded: number, downloaded: number }
+  | { status: "failed", error: string };
+
+interface RecordOutput {
+  id: string,
+  datetime: string,
+  host: string,
+  sync: SyncResult,
+}
+
 export default async function QuickNote(props: LaunchProps<{ draftValues: QuickNoteDraft, arguments: QuickNoteArgs }>) {
   const { cmdPath } = getPreferenceValues<Preferences>();
   const { arguments: { note } } = props;
 
-  const { stdout, stderr } = await promisify(execFile)(cmdPath, ["record", note]);
+  // the note is sent on stdin, so text such as `-` is never read as an argument
+  const record = promisify(execFile)(cmdPath, ["--output", "json", "record", "-"]);
+  record.child.stdin?.end(note);
+  const { stdout, stderr } = await record;
   console.log({ stdout, stderr });
+
+  const output: RecordOutput = JSON.parse(stdout);
+  if (output.sync.status === "failed") {
+    await showHUD(`Note saved, but sync failed: ${output.sync.error}`);
+  } else {
+    await showHUD("Note saved");
+  }
 }
@@ -1,14 +1,14 @@
-use std::{
-    collections::{BTreeMap, BTreeSet},
-    path::PathBuf,
-    sync::Mutex,
-    time::Duration,
-};
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
 
-use jiff::Zoned;
-use note_lsm_lib::{Client, RecordId, Settings};
-use serde::Serialize;
+use eyre::Context;
+use jiff::{civil::Date, Zoned};
+use note_lsm_lib::{
+    plan_rollups, to_markdown, Client, Config, ExportOptions, HostId, LevelPolicy, LevelStats,
+    NoteTree, RecordId, RollupPeriod, Settings, SkippedRecord,
+};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 use tauri::{async_runtime::spawn, Emitter, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 use tokio::time::sleep;
 
 #[derive(Serialize, Clone, Debug)]
@@ -16,160 +16,530 @@ pub struct Note {
     pub note: String,
     pub datetime: Zoned,
     pub children: Vec<RecordId>,
+    pub tags: Vec<String>,
+    #[serde(flatten)]
+    pub inline: Inline,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Inline {
+    pub hashtags: Vec<String>,
+    pub mentions: Vec<String>,
+    pub urls: Vec<String>,
+}
+
+impl From<note_lsm_lib::Inline> for Inline {
+    fn from(inline: note_lsm_lib::Inline) -> Self {
+        Self {
+            hashtags: inline.hashtags,
+            mentions: inline.mentions,
+            urls: inline.urls,
+        }
+    }
+}
+
+impl From<note_lsm_lib::Note> for Note {
+    fn from(note: note_lsm_lib::Note) -> Self {
+        let inline = note.inline().into();
+        Self {
+            note: note.note,
+            datetime: note.datetime,
+            children: note.children,
+            tags: note.tags,
+            inline,
+        }
+    }
+}
+
+/// An error returned from a tauri command, reported to the frontend as its kind and message.
+#[derive(Debug)]
+pub struct Error(eyre::Report);
+
+impl Error {
+    fn kind(&self) -> &'static str {
+        let Some(err) = self.0.downcast_ref::<note_lsm_lib::Error>() else {
+            return "other";
+        };
+        match err {
+            note_lsm_lib::Error::Store { .. } => "store",
+            note_lsm_lib::Error::Index { .. } => "index",
+            note_lsm_lib::Error::InvalidNotebook(_) => "invalid_notebook",
+            note_lsm_lib::Error::Config { .. }
+            | note_lsm_lib::Error::UnknownProfile(_)
+            | note_lsm_lib::Error::HomeDir
+            | note_lsm_lib::Error::Settings(_)
+            | note_lsm_lib::Error::HostId
+            | note_lsm_lib::Error::Key(_) => "setup",
+            note_lsm_lib::Error::Sync(_) => "sync",
+            note_lsm_lib::Error::Decrypt { .. } => "decrypt",
+            note_lsm_lib::Error::UnknownVersion { .. } => "unknown_version",
+            note_lsm_lib::Error::Decode { .. } => "decode",
+            note_lsm_lib::Error::TimeZone { .. } => "time_zone",
+            note_lsm_lib::Error::Import { .. } | note_lsm_lib::Error::Io { .. } => "import",
+            _ => "other",
+        }
+    }
+}
+
+impl From<eyre::Report> for Error {
+    fn from(err: eyre::Report) -> Self {
+        Self(err)
+    }
+}
+
+impl From<note_lsm_lib::Error> for Error {
+    fn from(err: note_lsm_lib::Error) -> Self {
+        Self(err.into())
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Error", 2)?;
+        s.serialize_field("kind", self.kind())?;
+        s.serialize_field("message", &format!("{:#}", self.0))?;
+        s.end()
+    }
 }
 
 struct AppState {
     settings: Settings,
+    /// The rollups to create in the background, shortest period first
+    rollups: Vec<RollupPeriod>,
+    /// The day the rollups were last created, as only the buckets before today are rolled up
+    rolled_up: Mutex<Option<Date>>,
+    levels: LevelPolicy,
     client: tokio::sync::OnceCell<tokio::sync::Mutex<Client>>,
     cache: Mutex<Cache>,
 }
 
 impl AppState {
-    async fn client(&self) -> &tokio::sync::Mutex<Client> {
+    /// Load the note-lsm config and atuin settings.
+    fn load() -> eyre::Result<Self> {
+        let config = Config::load(None, None)?;
+        let settings = config.settings()?;
+        let mut rollups = config.rollups;
+        rollups.sort();
+        rollups.dedup();
+
+        Ok(Self {
+            settings,
+            rollups,
+            rolled_up: Mutex::new(None),
+            levels: config.levels,
+            client: tokio::sync::OnceCell::new(),
+            cache: Mutex::new(Cache::default()),
+        })
+    }
+
+    async fn client(&self) -> eyre::Result<&tokio::sync::Mutex<Client>> {
         self.client
-            .get_or_init(|| async {
-                let client = Client::new(&self.settings).await;
-                tokio::sync::Mutex::new(client)
+            .get_or_try_init(|| async {
+                let client = Client::new(&self.settings).await?;
+                Ok(tokio::sync::Mutex::new(client))
             })
             .await
     }
 
-    async fn create_note(&self, note: Note) -> RecordId {
-        let client = self.client().await;
-        let id = client
+    async fn create_note(&self, note: note_lsm_lib::Note) -> eyre::Result<RecordId> {
+        let client = self.client().await?;
+        let mut client = client.lock().await;
+        let id = client.add_record(note.clone()).await?;
+        if self.settings.should_sync()? {
+            client.sync(&self.settings).await?;
+        }
+
+        self.cache
+            .lock()
+            .unwrap()
+            .tree
+            .insert(client.host_id(), id, note);
+        Ok(id)
+    }
+
+    /// Sync and create the rollups enabled in the config, returning whether the cache changed.
+    ///
+    /// This only does anything once a day, as a new bucket can only be rolled up once the day changes.
+    async fn rollup(&self) -> eyre::Result<bool> {
+        let now = Zoned::now();
+        if self.rollups.is_empty() || *self.rolled_up.lock().unwrap() == Some(now.date()) {
+            return Ok(false);
+        }
+
+        // sync first, so that buckets already rolled up by other hosts are skipped
+        let client = self.client().await?;
+        let host = {
+            let mut client = client.lock().await;
+            if self.settings.should_sync()? {
+                client.sync(&self.settings).await?;
+            }
+            client.host_id()
+        };
+        let mut changed = self.load_notes().await?;
+
+        for &period in &self.rollups {
+            let notes = {
+                let cache = self.cache.lock().unwrap();
+                plan_rollups(&cache.tree, period, host, &now)
+            };
+            for note in notes {
+                self.create_note(note).await.context("creating rollup")?;
+                changed = true;
+            }
+        }
+
+        *self.rolled_up.lock().unwrap() = Some(now.date());
+        Ok(changed)
+    }
+
+    /// Load any new notes into the cache, returning whether the cache changed.
+    async fn load_notes(&self) -> eyre::Result<bool> {
+        let client = self.client().await?;
+        let mut updated = false;
+        let skipped = client
             .lock()
             .await
-            .add_record(
-                note.note.clone(),
-                note.children.clone(),
-                note.datetime.clone(),
-            )
-            .await;
-        if self.settings.should_sync().unwrap() {
-            client.lock().await.sync(&self.settings).await;
+            .load_notes(|host, id, note| {
+                let mut cache = self.cache.lock().unwrap();
+                cache.tree.insert(host, id, note);
+                updated = true;
+            })
+            .await
+            .context("loading notes")?;
+
+        if !skipped.is_empty() {
+            let mut cache = self.cache.lock().unwrap();
+            cache.skipped.extend(skipped.into_iter().map(Skipped::from));
+            updated = true;
         }
 
-        self.cache.lock().unwrap().add_note(id, note);
-        id
+        Ok(updated)
+    }
+}
+
+/// A note record that could not be loaded, see [`SkippedRecord`].
+#[derive(Serialize, Clone, Debug)]
+pub struct Skipped {
+    pub id: RecordId,
+    pub host: HostId,
+    pub idx: u64,
+    pub reason: String,
+}
+
+impl From<SkippedRecord> for Skipped {
+    fn from(skipped: SkippedRecord) -> Self {
+        Self {
+            id: skipped.id,
+            host: skipped.host,
+            idx: skipped.idx,
+            reason: format!("{:#}", eyre::Report::from(skipped.reason)),
+        }
+    }
+}
+
+/// The notes at a level of the merge tree, see [`LevelStats`].
+#[derive(Serialize, Clone, Debug)]
+pub struct Level {
+    pub level: usize,
+    pub notes: usize,
+    pub unprocessed: usize,
+    pub budget: Option<usize>,
+    pub over_budget: bool,
+}
+
+impl From<LevelStats> for Level {
+    fn from(stats: LevelStats) -> Self {
+        Self {
+            over_budget: stats.over_budget(),
+            level: stats.level,
+            notes: stats.notes,
+            unprocessed: stats.unprocessed,
+            budget: stats.budget,
+        }
     }
 }
 
 #[derive(Default)]
 struct Cache {
-    map: BTreeMap<RecordId, Note>,
-    unprocessed: BTreeSet<RecordId>,
+    tree: NoteTree,
+    skipped: Vec<Skipped>,
+}
+
+#[tauri::command]
+async fn unprocessed(
+    tag: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<RecordId>, Error> {
+    if state.settings.should_sync()? {
+        let client = state.client().await?;
+        // the local notes are still worth showing when offline
+        if let Err(err) = client.lock().await.sync(&state.settings).await {
+            eprintln!("failed to sync: {:#}", eyre::Report::from(err));
+        }
+    }
+    state.load_notes().await?;
+
+    let state = state.cache.lock().unwrap();
+
+    let mut query = state.tree.query().unprocessed();
+    if let Some(tag) = &tag {
+        query = query.tag(tag);
+    }
+    Ok(query.iter().rev().map(|(_, id, _)| id).collect())
 }
 
-impl Cache {
-    fn add_note(&mut self, id: RecordId, note: Note) {
-        self.unprocessed.insert(id);
-        for child in &note.children {
-            self.unprocessed.remove(child);
+/// Group the unprocessed notes by the hashtags (`#tag`) and mentions (`@name`) in their text.
+///
+/// Notes appear once under each hashtag and mention they contain.
+#[tauri::command]
+async fn unprocessed_groups(
+    state: tauri::State<'_, AppState>,
+) -> Result<BTreeMap<String, Vec<RecordId>>, Error> {
+    let state = state.cache.lock().unwrap();
+
+    let mut groups = BTreeMap::<String, Vec<RecordId>>::new();
+    for id in state.tree.unprocessed().rev() {
+        let Some(note) = state.tree.get(id) else {
+            continue;
+        };
+        let inline = note.inline();
+        let hashtags = inline.hashtags.iter().map(|tag| format!("#{tag}"));
+        let mentions = inline.mentions.iter().map(|name| format!("@{name}"));
+        for key in hashtags.chain(mentions) {
+            groups.entry(key).or_default().push(id);
         }
-        self.map.insert(id, note);
     }
+
+    Ok(groups)
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SearchHit {
+    pub id: RecordId,
+    pub snippet: String,
+    pub rank: f64,
 }
 
 #[tauri::command]
-async fn unprocessed(state: tauri::State<'_, AppState>) -> Result<Vec<RecordId>, ()> {
-    let client = state.client().await;
-    client.lock().await.sync(&state.settings).await;
-    client
+async fn search(
+    query: String,
+    limit: Option<u32>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<SearchHit>, Error> {
+    let client = state.client().await?;
+    let hits = client
         .lock()
         .await
-        .load_notes(|_host, id, note| {
-            let mut cache = state.cache.lock().unwrap();
-            cache.add_note(
-                id,
-                Note {
-                    note: note.note,
-                    datetime: note.datetime,
-                    children: note.children,
-                },
-            );
+        .search(&query, limit.unwrap_or(50))
+        .await?;
+    Ok(hits
+        .into_iter()
+        .map(|hit| SearchHit {
+            id: hit.id,
+            snippet: hit.snippet,
+            rank: hit.rank,
         })
-        .await
-        .unwrap();
+        .collect())
+}
 
-    let state = state.cache.lock().unwrap();
+#[tauri::command]
+async fn tags(state: tauri::State<'_, AppState>) -> Result<BTreeMap<String, usize>, Error> {
+    let client = state.client().await?;
+    let client = client.lock().await;
+    Ok(client.tags().clone())
+}
+
+#[tauri::command]
+async fn level_stats(state: tauri::State<'_, AppState>) -> Result<Vec<Level>, Error> {
+    let cache = state.cache.lock().unwrap();
+    Ok(state
+        .levels
+        .stats(&cache.tree)
+        .into_iter()
+        .map(Level::from)
+        .collect())
+}
+
+#[tauri::command]
+async fn notebooks(state: tauri::State<'_, AppState>) -> Result<Vec<String>, Error> {
+    let client = state.client().await?;
+    let notebooks = client.lock().await.notebooks().await?;
+    Ok(notebooks)
+}
+
+#[tauri::command]
+async fn current_notebook(state: tauri::State<'_, AppState>) -> Result<String, Error> {
+    let client = state.client().await?;
+    let notebook = client.lock().await.notebook().to_owned();
+    Ok(notebook)
+}
+
+#[tauri::command]
+async fn create_notebook(name: String, state: tauri::State<'_, AppState>) -> Result<(), Error> {
+    let client = state.client().await?;
+    let mut client = client.lock().await;
+    client.create_notebook(&name).await?;
+    if state.settings.should_sync()? {
+        client.sync(&state.settings).await?;
+    }
+    Ok(())
+}
+
+/// Switch to another notebook, dropping all notes loaded from the current one.
+#[tauri::command]
+async fn switch_notebook(name: String, state: tauri::State<'_, AppState>) -> Result<(), Error> {
+    let client = state.client().await?;
+    let mut client = client.lock().await;
+    *client = Client::with_notebook(&state.settings, &name).await?;
+    *state.cache.lock().unwrap() = Cache::default();
+    Ok(())
+}
+
+/// Export notes as Markdown to a file chosen by the user.
+///
+/// Returns the path written to, or `None` if the user cancelled.
+#[tauri::command]
+async fn export_markdown(
+    root: Option<RecordId>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<String>, Error> {
+    let markdown = {
+        let cache = state.cache.lock().unwrap();
+        let options = ExportOptions {
+            root,
+            ..ExportOptions::default()
+        };
+        to_markdown(&cache.tree, &options)
+    };
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .add_filter("Markdown", &["md"])
+        .set_file_name("notes.md")
+        .save_file(move |path| {
+            let _ = tx.send(path);
+        });
+    let Some(path) = rx.await.context("waiting for export file")? else {
+        return Ok(None);
+    };
 
-    Ok(state.unprocessed.iter().rev().copied().collect())
+    let path = path.into_path().context("resolving export file")?;
+    std::fs::write(&path, markdown)
+        .with_context(|| format!("writing export to {}", path.display()))?;
+    Ok(Some(path.display().to_string()))
 }
 
 #[tauri::command]
-async fn get_note(id: RecordId, state: tauri::State<'_, AppState>) -> Result<Note, ()> {
+async fn get_note(id: RecordId, state: tauri::State<'_, AppState>) -> Result<Note, Error> {
     let state = state.cache.lock().unwrap();
-    state.map.get(&id).cloned().ok_or(())
+    state
+        .tree
+        .get(id)
+        .cloned()
+        .map(Note::from)
+        .ok_or_else(|| Error(eyre::eyre!("note {} not found", id.0)))
+}
+
+#[tauri::command]
+async fn skipped_records(state: tauri::State<'_, AppState>) -> Result<Vec<Skipped>, Error> {
+    let state = state.cache.lock().unwrap();
+    Ok(state.skipped.clone())
 }
 
 #[tauri::command]
 async fn add_note(
     note: String,
     children: Vec<RecordId>,
+    tags: Option<Vec<String>>,
     state: tauri::State<'_, AppState>,
-) -> Result<RecordId, ()> {
+) -> Result<RecordId, Error> {
     Ok(state
-        .create_note(Note {
+        .create_note(note_lsm_lib::Note {
             note,
             datetime: Zoned::now(),
             children,
+            tags: tags.unwrap_or_default(),
+            retracts: vec![],
         })
-        .await)
+        .await?)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let mut settings = note_lsm_lib::Settings::new().unwrap();
-
-    settings.record_store_path = PathBuf::from(std::env::var_os("HOME").expect("$HOME not found"))
-        .join(".local")
-        .join("share")
-        .join("notelsm")
-        .join("store.db")
-        .to_str()
-        .unwrap()
-        .to_owned();
-
-    let state = AppState {
-        settings,
-        client: tokio::sync::OnceCell::new(),
-        cache: Mutex::new(Cache::default()),
+    let (state, load_error) = match AppState::load() {
+        Ok(state) => (Some(state), None),
+        Err(err) => (None, Some(format!("{err:#}"))),
     };
 
-    tauri::Builder::default()
+    let mut builder = tauri::Builder::default();
+    if let Some(state) = state {
+        builder = builder.manage(state);
+    }
+
+    builder
         .plugin(tauri_plugin_shell::init())
-        .invoke_handler(tauri::generate_handler![unprocessed, get_note, add_note])
-        .manage(state)
-        .setup(|app| {
+        .plugin(tauri_plugin_dialog::init())
+        .invoke_handler(tauri::generate_handler![
+            unprocessed,
+            unprocessed_groups,
+            search,
+            tags,
+            level_stats,
+            get_note,
+            skipped_records,
+            add_note,
+            notebooks,
+            current_notebook,
+            create_notebook,
+            switch_notebook,
+            export_markdown
+        ])
+        .setup(move |app| {
             let handle = app.handle().clone();
+            if let Some(err) = load_error {
+                // without a config there is no store to open, so explain why before quitting
+                app.dialog()
+                    .message(format!("Could not load the note-lsm config:\n\n{err}"))
+                    .title("note-lsm")
+                    .kind(MessageDialogKind::Error)
+                    .show(move |_| handle.exit(1));
+                return Ok(());
+            }
+
             spawn(async move {
                 let state = handle.state::<AppState>();
-                let client = state.client().await;
                 loop {
-                    let mut updated = false;
-                    client
-                        .lock()
-                        .await
-                        .load_notes(|_host, id, note| {
-                            let mut cache = state.cache.lock().unwrap();
-                            cache.add_note(
-                                id,
-                                Note {
-                                    note: note.note,
-                                    datetime: note.datetime,
-                                    children: note.children,
-                                },
-                            );
-                            updated = true;
-                        })
-                        .await
-                        .unwrap();
+                    let updated = match state.load_notes().await {
+                        // rollups need all notes to be loaded, or they would miss some
+                        Ok(updated) => match state.rollup().await {
+                            Ok(created) => updated || created,
+                            Err(err) => {
+                                eprintln!("failed to create rollups: {err:#}");
+                                updated
+                            }
+                        },
+                        Err(err) => {
+                            eprintln!("failed to load notes: {err:#}");
+                            false
+                        }
+                    };
 
                     if updated {
                         let cache = state.cache.lock().unwrap();
-                        let unprocessed: Vec<RecordId> =
-                            cache.unprocessed.iter().rev().copied().collect();
+                        let unprocessed: Vec<RecordId> = cache.tree.unprocessed().rev().collect();
                         handle.emit("new-notes", unprocessed).unwrap();
+                        handle
+                            .emit("skipped-records", cache.skipped.clone())
+                            .unwrap();
+                        let levels: Vec<Level> = state
+                            .levels
+                            .stats(&cache.tree)
+                            .into_iter()
+                            .map(Level::from)
+                            .collect();
+                        handle.emit("level-stats", levels).unwrap();
                     }
 
                     sleep(Duration::from_secs(10)).await;
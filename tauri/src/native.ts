@@ -5,25 +5,116 @@ interface NoteInner {
     note: string;
     datetime: string;
     children: string[];
+    tags: string[];
+    hashtags: string[];
+    mentions: string[];
+    urls: string[];
 }
 
 export interface Note {
     note: string;
     datetime: Temporal.ZonedDateTime;
     children: string[];
+    tags: string[];
+    hashtags: string[];
+    mentions: string[];
+    urls: string[];
+}
+
+export interface CommandError {
+    kind: "store" | "index" | "setup" | "sync" | "decrypt" | "unknown_version" | "decode" | "time_zone" | "invalid_notebook" | "import" | "other";
+    message: string;
+}
+
+export function errorMessage(e: unknown): string {
+    if (typeof e === "object" && e !== null && "message" in e) {
+        return (e as CommandError).message;
+    }
+    return String(e);
 }
 
 export async function getNote(id: string): Promise<Note> {
-    let { note, datetime, children } = await invoke<NoteInner>("get_note", { id });
+    let { datetime, ...rest } = await invoke<NoteInner>("get_note", { id });
     return {
-        note, children, datetime: Temporal.ZonedDateTime.from(datetime),
+        ...rest, datetime: Temporal.ZonedDateTime.from(datetime),
     };
 }
 
-export async function unprocessed(): Promise<string[]> {
-    return await invoke<string[]>("unprocessed", {})
+export async function unprocessed(tag?: string): Promise<string[]> {
+    return await invoke<string[]>("unprocessed", { tag })
+}
+
+/** Unprocessed notes grouped by `#hashtag` and `@mention`. */
+export async function unprocessedGroups(): Promise<Record<string, string[]>> {
+    return await invoke<Record<string, string[]>>("unprocessed_groups", {})
+}
+
+export interface SearchHit {
+    id: string;
+    /** Matched terms are wrapped in `[` `]`. */
+    snippet: string;
+    rank: number;
+}
+
+export async function search(query: string, limit?: number): Promise<SearchHit[]> {
+    return await invoke<SearchHit[]>("search", { query, limit })
+}
+
+export async function tags(): Promise<Record<string, number>> {
+    return await invoke<Record<string, number>>("tags", {})
+}
+
+/** The notes at a level of the merge tree: 0 for quick notes, 1 for merges of those, and so on. */
+export interface Level {
+    level: number;
+    notes: number;
+    unprocessed: number;
+    budget: number | null;
+    /** More notes at this level are waiting to be merged than its budget allows. */
+    over_budget: boolean;
+}
+
+export async function levelStats(): Promise<Level[]> {
+    return await invoke<Level[]>("level_stats", {})
+}
+
+export interface SkippedRecord {
+    id: string;
+    host: string;
+    idx: number;
+    reason: string;
+}
+
+export async function skippedRecords(): Promise<SkippedRecord[]> {
+    return await invoke<SkippedRecord[]>("skipped_records", {})
+}
+
+export async function addNote(note: string, children: string[], tags: string[] = []): Promise<string> {
+    return await invoke<string>("add_note", { note, children, tags })
+}
+
+export async function notebooks(): Promise<string[]> {
+    return await invoke<string[]>("notebooks", {})
+}
+
+export async function currentNotebook(): Promise<string> {
+    return await invoke<string>("current_notebook", {})
+}
+
+export async function createNotebook(name: string): Promise<void> {
+    await invoke("create_notebook", { name })
+}
+
+/** Switch to another notebook. Notes from the previous notebook are no longer returned. */
+export async function switchNotebook(name: string): Promise<void> {
+    await invoke("switch_notebook", { name })
 }
 
-export async function addNote(note: string, children: string[]): Promise<string> {
-    return await invoke<string>("add_note", { note, children })
+/**
+ * Export notes as Markdown to a file chosen by the user, optionally only the given note and the notes merged into it.
+ *
+ * Returns the path written to, or `null` if the user cancelled.
+ */
+export async function exportMarkdown(root?: string): Promise<string | null> {
+    return await invoke<string | null>("export_markdown", { root })
 }